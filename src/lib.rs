@@ -1,3 +1,6 @@
 #[macro_use]
 extern crate bitflags;
+pub mod testing;
 pub mod tws;
+
+pub use tws::error::Error;
@@ -12,7 +12,7 @@ use tracing_subscriber::{self, EnvFilter};
 
 //use futures::stream::StreamExt;
 use tws::{
-    client::ClientImpl,
+    client::{ClientImpl, Response},
     messages::{
         BarSize, HistoricalDataType, TWSIncommingMessage, TWSIncommingMessageImpl, TickType,
     },
@@ -78,6 +78,61 @@ fn handle_msg(ent: &RillrateData, msg: &TWSIncommingMessageImpl) {
     }
 }
 
+struct PnlData {
+    daily: Pulse,
+    unrealized: Pulse,
+    realized: Pulse,
+}
+
+impl PnlData {
+    fn new(name: &str) -> PnlData {
+        PnlData {
+            daily: Pulse::create(format!("{}.pnl.daily", name).as_str(), Some(5000)).unwrap(),
+            unrealized: Pulse::create(format!("{}.pnl.unrealized", name).as_str(), Some(5000))
+                .unwrap(),
+            realized: Pulse::create(format!("{}.pnl.realized", name).as_str(), Some(5000)).unwrap(),
+        }
+    }
+}
+
+fn spawn_pnl(ic: Arc<ClientImpl>, account: String) {
+    tokio::spawn(async move {
+        let ent = PnlData::new(account.as_str());
+        let mut reader = ic.req_pnl(&account, "").await.unwrap();
+
+        while let Some(msg) = reader.recv().await {
+            let msg = match msg {
+                Ok(Response::Message(msg)) => msg,
+                Err(e) => {
+                    error!(%e, %account, "PnL request failed");
+                    break;
+                }
+                Ok(other) => {
+                    warn!(?other, %account, "Connection state changed");
+                    continue;
+                }
+            };
+            if let TWSIncommingMessageImpl::PnL {
+                daily_pnl,
+                unrealized_pnl,
+                realized_pnl,
+                ..
+            } = *msg.get_msg()
+            {
+                if let Some(v) = daily_pnl {
+                    ent.daily.set(v);
+                }
+                if let Some(v) = unrealized_pnl {
+                    ent.unrealized.set(v);
+                }
+                if let Some(v) = realized_pnl {
+                    ent.realized.set(v);
+                }
+            }
+        }
+    });
+}
+
 fn spawn_market_req_data(
     ic: Arc<ClientImpl>,
     im: Arc<RwLock<HashMap<i32, RillrateData>>>,
@@ -94,6 +149,17 @@ fn spawn_market_req_data(
         let mut reader = ic.req_market_data(contract).await.unwrap();
 
         while let Some(msg) = reader.recv().await {
+            let msg = match msg {
+                Ok(Response::Message(msg)) => msg,
+                Err(e) => {
+                    error!(%e, %name, "Market data request failed");
+                    break;
+                }
+                Ok(other) => {
+                    warn!(?other, %name, "Connection state changed");
+                    continue;
+                }
+            };
             let msg = msg.get_msg();
             match &msg {
                 e @ &TWSIncommingMessageImpl::Error { .. } => println!("{:?}", e),
@@ -140,6 +206,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let map = Arc::new(RwLock::new(HashMap::new()));
 
+    if let Ok(account) = std::env::var("TWS_ACCOUNT") {
+        spawn_pnl(client.clone(), account);
+    }
+
     /* {
         let ic = client.clone();
         let mut contract = Contract::default();
@@ -151,16 +221,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
         contract.strike = 750.0;
         contract.last_trade_date_or_contract_month = "20210423".to_owned();
         contract.right = "C".to_owned();
-        let mut reader = ic
-            .req_histogram_data(&contract, true, "3 days")
-            .await
-            .unwrap();
-
-        if let Some(msg) = reader.recv().await {
-            let msg = msg.get_msg();
-            println!("{:?}", msg);
-        } else {
-            println!("failed to request historical data :(");
+        match ic.req_histogram_data(&contract, true, "3 days").await {
+            Ok(histogram) => println!("{:?}", histogram),
+            Err(e) => println!("failed to request histogram data: {}", e),
         }
     } */
     {
@@ -187,7 +250,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
             .await
             .unwrap();
 
-        while let Some(msg) = reader.recv().await {
+        while let Some(Ok(Response::Message(msg))) = reader.recv().await {
             let msg = msg.get_msg();
             //println!("{:?}", msg);
         } /* else {
@@ -205,7 +268,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
             .unwrap();
         let mut rstrikes = HashSet::new();
         let mut rexp = HashSet::new();
-        while let Some(msg) = reader.recv().await {
+        while let Some(Ok(Response::Message(msg))) = reader.recv().await {
             let msg = msg.get_msg();
             //println!("{:?}", msg);
             match msg {
@@ -261,8 +324,6 @@ async fn main() -> Result<(), Box<dyn Error>> {
             spawn_market_req_data(client.clone(), map.clone(), contract.clone());
             contract.right = "P".to_owned();
             spawn_market_req_data(client.clone(), map.clone(), contract);
-
-            tokio::time::sleep(std::time::Duration::from_millis(200)).await
         }
     }
 
@@ -0,0 +1,783 @@
+//! A local stand-in for TWS or IB Gateway to test clients against.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex as SyncMutex},
+};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
+use tokio_util::codec::Framed;
+use tracing::{debug, warn};
+
+use crate::tws::{
+    codec::{DecodedMessage, TWSCodec},
+    versions::MAX_SERVER_VERSION,
+};
+
+type Handler = Box<dyn Fn(&DecodedMessage) -> Vec<DecodedMessage> + Send + Sync>;
+
+enum Command {
+    Send(DecodedMessage),
+    Disconnect,
+}
+
+#[derive(Default)]
+struct State {
+    //keyed by the message id, the first field of a request
+    handlers: HashMap<String, Handler>,
+    connection: Option<mpsc::UnboundedSender<Command>>,
+    connections: usize,
+}
+
+/// Builds a frame from its fields.
+pub fn frame(fields: &[&str]) -> DecodedMessage {
+    fields
+        .iter()
+        .map(|f| Bytes::copy_from_slice(f.as_bytes()))
+        .collect()
+}
+
+/// Listens on a local port, performs the `API\0` handshake with every client
+/// that connects and answers their requests with scripted frames. Only the
+/// latest connection is talked to.
+pub struct MockGateway {
+    addr: SocketAddr,
+    state: Arc<SyncMutex<State>>,
+    requests: Mutex<mpsc::UnboundedReceiver<DecodedMessage>>,
+    accept: JoinHandle<()>,
+}
+
+impl MockGateway {
+    /// Starts a gateway speaking every server version the client does that
+    /// hands out order ids from 1.
+    pub async fn start() -> io::Result<MockGateway> {
+        MockGateway::start_with(MAX_SERVER_VERSION, 1).await
+    }
+
+    /// Starts a gateway speaking server versions up to `server_version` that
+    /// hands out order ids from `next_order_id`.
+    pub async fn start_with(server_version: i32, next_order_id: i32) -> io::Result<MockGateway> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(SyncMutex::new(State::default()));
+        let (requests_tx, requests) = mpsc::unbounded_channel();
+
+        let accept_state = state.clone();
+        let accept = tokio::spawn(async move {
+            loop {
+                let tcp = match listener.accept().await {
+                    Ok((tcp, _)) => tcp,
+                    Err(e) => {
+                        warn!(?e, "Mock gateway failed to accept");
+                        continue;
+                    }
+                };
+                let (commands_tx, commands) = mpsc::unbounded_channel();
+                {
+                    let mut state = accept_state.lock().unwrap();
+                    //a new client replaces the old one
+                    if let Some(old) = state.connection.replace(commands_tx) {
+                        let _ = old.send(Command::Disconnect);
+                    }
+                    state.connections += 1;
+                }
+                tokio::spawn(serve(
+                    tcp,
+                    server_version,
+                    next_order_id,
+                    accept_state.clone(),
+                    requests_tx.clone(),
+                    commands,
+                ));
+            }
+        });
+
+        Ok(MockGateway {
+            addr,
+            state,
+            requests: Mutex::new(requests),
+            accept,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Answers every request whose message id is `msg_id` with the frames
+    /// `handler` returns for it, replacing any earlier handler for it.
+    pub fn respond<F>(&self, msg_id: &str, handler: F)
+    where
+        F: Fn(&DecodedMessage) -> Vec<DecodedMessage> + Send + Sync + 'static,
+    {
+        self.state
+            .lock()
+            .unwrap()
+            .handlers
+            .insert(msg_id.to_owned(), Box::new(handler));
+    }
+
+    /// Answers every request whose message id is `msg_id` with recorded
+    /// frames, sent as they are.
+    pub fn respond_with(&self, msg_id: &str, frames: Vec<DecodedMessage>) {
+        self.respond(msg_id, move |_| frames.clone());
+    }
+
+    /// Sends an unsolicited frame, returns false if no client is connected.
+    pub fn send(&self, frame: DecodedMessage) -> bool {
+        self.command(Command::Send(frame))
+    }
+
+    /// Sends an `Error` message for `req_id`, -1 for a notice.
+    pub fn send_error(&self, req_id: i32, code: i32, msg: &str) -> bool {
+        self.send(frame(&[
+            "4",
+            "2",
+            &req_id.to_string(),
+            &code.to_string(),
+            msg,
+        ]))
+    }
+
+    /// Closes the connection to the client, which may connect again.
+    pub fn disconnect(&self) -> bool {
+        let connection = self.state.lock().unwrap().connection.take();
+        connection.is_some_and(|c| c.send(Command::Disconnect).is_ok())
+    }
+
+    /// How many clients connected so far.
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    /// Waits for the next request any client sent after its handshake.
+    pub async fn next_request(&self) -> Option<DecodedMessage> {
+        self.requests.lock().await.recv().await
+    }
+
+    /// Waits for the next request with message id `msg_id`, skipping others.
+    pub async fn expect(&self, msg_id: &str) -> Option<DecodedMessage> {
+        loop {
+            let request = self.next_request().await?;
+            if request.first().is_some_and(|id| id == msg_id) {
+                return Some(request);
+            }
+        }
+    }
+
+    fn command(&self, command: Command) -> bool {
+        match &self.state.lock().unwrap().connection {
+            Some(c) => c.send(command).is_ok(),
+            None => false,
+        }
+    }
+}
+
+impl Drop for MockGateway {
+    fn drop(&mut self) {
+        self.accept.abort();
+        self.disconnect();
+    }
+}
+
+//reads the `API\0` prefix and the client's `vMIN..MAX` version range
+async fn read_version_range(tcp: &mut TcpStream) -> io::Result<(i32, i32)> {
+    let mut prefix = [0u8; 4];
+    tcp.read_exact(&mut prefix).await?;
+    if &prefix != b"API\0" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing API prefix",
+        ));
+    }
+    let len = tcp.read_u32().await? as usize;
+    let mut range = vec![0u8; len];
+    tcp.read_exact(&mut range).await?;
+    let range = String::from_utf8_lossy(&range);
+    let parsed = range
+        .trim_start_matches('v')
+        .split_once("..")
+        .and_then(|(min, max)| Some((min.parse().ok()?, max.parse().ok()?)));
+    parsed.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, range.into_owned()))
+}
+
+async fn serve(
+    mut tcp: TcpStream,
+    server_version: i32,
+    next_order_id: i32,
+    state: Arc<SyncMutex<State>>,
+    requests: mpsc::UnboundedSender<DecodedMessage>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+) {
+    let (min, max) = match read_version_range(&mut tcp).await {
+        Ok(range) => range,
+        Err(e) => {
+            warn!(?e, "Mock gateway got a bad handshake");
+            return;
+        }
+    };
+    //like TWS answer with the newest version both speak, a client that
+    //only speaks newer ones gets it anyway and has to give up
+    let version = max.min(server_version);
+    debug!(min, max, version, "Mock gateway accepted client");
+
+    let mut framed = Framed::new(tcp, TWSCodec::new());
+    if write(
+        &mut framed,
+        frame(&[&version.to_string(), "20210309 22:54:30 CET"]),
+    )
+    .await
+    .is_err()
+    {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            request = framed.next() => match request {
+                Some(Ok(request)) => {
+                    let request = request.to_fields();
+                    let replies = match request.first().map(|id| &id[..]) {
+                        //StartApi
+                        Some(b"71") => vec![frame(&["9", "1", &next_order_id.to_string()])],
+                        Some(id) => {
+                            let id = String::from_utf8_lossy(id).into_owned();
+                            let state = state.lock().unwrap();
+                            state.handlers.get(&id).map(|h| h(&request)).unwrap_or_default()
+                        }
+                        None => vec![],
+                    };
+                    let _ = requests.send(request);
+                    for reply in replies {
+                        if write(&mut framed, reply).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Some(Err(e)) => {
+                    warn!(?e, "Mock gateway failed to read request");
+                    return;
+                }
+                None => return,
+            },
+            command = commands.recv() => match command {
+                Some(Command::Send(frame)) => {
+                    if write(&mut framed, frame).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Command::Disconnect) | None => return,
+            },
+        }
+    }
+}
+
+async fn write(
+    framed: &mut Framed<TcpStream, TWSCodec>,
+    mut frame: DecodedMessage,
+) -> Result<(), crate::Error> {
+    //TWS terminates every field, including the last
+    frame.push(Bytes::new());
+    framed.send(frame).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame, MockGateway};
+    use crate::tws::{
+        client::{ClientImpl, Response},
+        messages::{
+            tests::OPEN_ORDER, AccountSummaryTag, Action, HistoricalDataType, HistoricalTicks,
+            Order, TWSIncommingMessageImpl,
+        },
+        rate_limit::RateLimit,
+        versions,
+    };
+    use crate::Error;
+    use chrono::{TimeZone, Utc};
+    use fixed_macro::types::I44F20 as dec;
+    use twsapi::core::contract::Contract;
+
+    #[tokio::test]
+    async fn answers_requests_and_errors() {
+        let gateway = MockGateway::start_with(versions::COMPLETED_ORDERS, 42)
+            .await
+            .unwrap();
+        gateway.respond("87", |request| {
+            let req_id = String::from_utf8_lossy(&request[1]).into_owned();
+            vec![frame(&["88", &req_id, "1618812000"])]
+        });
+
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        assert_eq!(versions::COMPLETED_ORDERS, client.server_version());
+        assert_eq!("20210309 22:54:30 CET", client.connection_time());
+        assert_eq!(1, gateway.connections());
+
+        let contract = Contract::default();
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            head = client.req_head_timestamp(&contract, HistoricalDataType::Trades, true) => {
+                assert_eq!(Utc.timestamp_opt(1618812000, 0).unwrap(), head.unwrap());
+            }
+        }
+        assert_eq!(&b"87"[..], &gateway.expect("87").await.unwrap()[0][..]);
+
+        gateway.respond("87", |request| {
+            let req_id = String::from_utf8_lossy(&request[1]).into_owned();
+            vec![frame(&["4", "2", &req_id, "162", "no head time stamp"])]
+        });
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            head = client.req_head_timestamp(&contract, HistoricalDataType::Midpoint, true) => {
+                assert!(matches!(head, Err(Error::Tws { code: 162, .. })), "{:?}", head);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn client_reconnects_after_disconnect() {
+        let gateway = MockGateway::start().await.unwrap();
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        assert!(gateway.disconnect());
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            //StartApi of the second connection
+            _ = async {
+                gateway.expect("71").await;
+                gateway.expect("71").await;
+            } => assert_eq!(2, gateway.connections()),
+        }
+    }
+
+    #[tokio::test]
+    async fn speaks_older_server_versions() {
+        let gateway = MockGateway::start_with(versions::COMPLETED_ORDERS, 7)
+            .await
+            .unwrap();
+        //150 doesn't know use_price_mgmt_algo, the last field
+        let open_order = &OPEN_ORDER[..OPEN_ORDER.len() - 1];
+        gateway.respond_with("3", vec![frame(open_order)]);
+
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        let contract = Contract::default();
+        let order = Order::limit(Action::Buy, 100.0, dec!(150.25));
+        let mut orders = client.place_order(&contract, &order).await.unwrap();
+        assert_eq!(7, orders.order_id());
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            msg = orders.recv() => match msg {
+                Some(Ok(Response::Message(msg))) => assert!(matches!(
+                    msg.get_msg(),
+                    TWSIncommingMessageImpl::OpenOrder { order_id: 7, .. }
+                )),
+                other => panic!("unexpected {:?}", other),
+            },
+        }
+        //ends with discretionary_up_to_limit_price instead
+        let request = gateway.expect("3").await.unwrap();
+        assert_eq!(&b"0"[..], &request.last().unwrap()[..]);
+    }
+
+    #[tokio::test]
+    async fn inactive_orders_end_their_subscription() {
+        let gateway = MockGateway::start_with(versions::MAX_SERVER_VERSION, 7)
+            .await
+            .unwrap();
+        gateway.respond_with(
+            "3",
+            vec![frame(&[
+                "3", "7", "Inactive", "0", "100", "0", "0", "0", "0", "0", "", "0",
+            ])],
+        );
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        let order = Order::limit(Action::Buy, 100.0, dec!(150.25));
+        let mut orders = client
+            .place_order(&Contract::default(), &order)
+            .await
+            .unwrap();
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            _ = async {
+                assert!(matches!(orders.recv().await, Some(Ok(Response::Message(_)))));
+                assert!(orders.recv().await.is_none());
+            } => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn open_orders_ignore_earlier_end_markers() {
+        let gateway = MockGateway::start().await.unwrap();
+        gateway.respond_with("16", vec![frame(OPEN_ORDER), frame(&["53", "1"])]);
+        //a single token, so the request waits while the client reads on
+        let rate_limit = RateLimit {
+            per_second: 5.0,
+            burst: 1,
+        };
+        let client = ClientImpl::with_rate_limit(gateway.addr(), rate_limit)
+            .await
+            .unwrap();
+        client.req_ids().await.unwrap();
+        //like the one TWS sends on connect
+        assert!(gateway.send(frame(&["53", "1"])));
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            orders = client.open_orders() => {
+                let orders = orders.unwrap();
+                assert_eq!(vec![7], orders.iter().map(|o| o.order_id).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn open_orders_fail_when_the_connection_drops() {
+        let gateway = MockGateway::start().await.unwrap();
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            (orders, _) = async {
+                tokio::join!(client.open_orders(), async {
+                    gateway.expect("16").await.unwrap();
+                    assert!(gateway.disconnect());
+                })
+            } => {
+                assert!(
+                    matches!(&orders, Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::ConnectionAborted),
+                    "{:?}",
+                    orders
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn abandoned_open_orders_dont_end_the_next() {
+        let gateway = MockGateway::start().await.unwrap();
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        let mut other_order = OPEN_ORDER.to_vec();
+        other_order[1] = "8";
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            orders = async {
+                tokio::select! {
+                    _ = client.open_orders() => panic!("first collection ended"),
+                    _ = gateway.expect("16") => {}
+                }
+                //TWS answers in order, the abandoned request comes in late but
+                //before the next one
+                tokio::join!(client.open_orders(), async {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    assert!(gateway.send(frame(OPEN_ORDER)));
+                    assert!(gateway.send(frame(&["53", "1"])));
+                    gateway.expect("16").await.unwrap();
+                    assert!(gateway.send(frame(&other_order)));
+                    assert!(gateway.send(frame(&["53", "1"])));
+                })
+                .0
+            } => {
+                let orders = orders.unwrap();
+                assert_eq!(vec![8], orders.iter().map(|o| o.order_id).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn paced_requests_only_hold_back_their_contract() {
+        let gateway = MockGateway::start().await.unwrap();
+        gateway.respond("87", |request| {
+            let req_id = String::from_utf8_lossy(&request[1]).into_owned();
+            vec![frame(&["88", &req_id, "1618812000"])]
+        });
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        let aapl = Contract {
+            symbol: "AAPL".to_owned(),
+            ..Contract::default()
+        };
+        let msft = Contract {
+            symbol: "MSFT".to_owned(),
+            ..Contract::default()
+        };
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            _ = async {
+                client.req_head_timestamp(&aapl, HistoricalDataType::Trades, true).await.unwrap();
+                //the same request again has to wait 15s, another contract doesn't
+                tokio::select! {
+                    _ = client.req_head_timestamp(&aapl, HistoricalDataType::Trades, true) => {
+                        panic!("identical request wasn't paced")
+                    }
+                    head = tokio::time::timeout(
+                        std::time::Duration::from_secs(5),
+                        client.req_head_timestamp(&msft, HistoricalDataType::Trades, true),
+                    ) => {
+                        head.expect("held back by another contract").unwrap();
+                    }
+                }
+            } => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_historical_requests_through_pacing() {
+        let gateway = MockGateway::start().await.unwrap();
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        let contract = Contract::default();
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            _ = async {
+                let _ticks = client
+                    .req_historical_ticks(&contract, "20210419 09:00:00", "", 10, HistoricalDataType::Midpoint, true, false)
+                    .await
+                    .unwrap();
+                gateway.expect("96").await.unwrap();
+                assert!(gateway.disconnect());
+                gateway.expect("71").await.unwrap();
+                //identical to the one sent before the disconnect, so it waits
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                assert_eq!(1, client.pacing_status().queued);
+            } => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn position_book_values_positions_from_account_updates() {
+        let gateway = MockGateway::start().await.unwrap();
+        let contract = ["265598", "AAPL", "STK", "", "0", "", ""];
+        let mut position = vec!["61", "3", "DU123"];
+        position.extend(contract);
+        position.extend(["NASDAQ", "USD", "AAPL", "NMS", "100", "134.5"]);
+        let mut portfolio_value = vec!["7", "8"];
+        portfolio_value.extend(contract);
+        portfolio_value.extend(["NASDAQ", "USD", "AAPL", "NMS", "100", "135.5"]);
+        portfolio_value.extend(["13550", "134.5", "100", "0", "DU123"]);
+        gateway.respond_with("61", vec![frame(&position), frame(&["62", "1"])]);
+        gateway.respond_with("6", vec![frame(&portfolio_value)]);
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            _ = async {
+                let mut book = client.req_position_book(Some("DU123")).await.unwrap();
+                loop {
+                    let changes = book.next().await.unwrap().unwrap();
+                    if changes.iter().any(|c| c.after.as_ref().is_some_and(|e| e.market_value.is_some())) {
+                        break;
+                    }
+                }
+                let entry = book.book().get("DU123", "", 265598).unwrap();
+                assert_eq!(100.0, entry.position);
+                assert_eq!(Some(135.5), entry.market_price);
+                assert_eq!(Some(13550.0), entry.market_value);
+                assert_eq!(Some(100.0), entry.unrealized_pnl);
+            } => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn account_summary_states_track_each_account() {
+        let gateway = MockGateway::start().await.unwrap();
+        gateway.respond("62", |request| {
+            let req_id = String::from_utf8_lossy(&request[2]).into_owned();
+            vec![
+                frame(&[
+                    "63",
+                    "1",
+                    &req_id,
+                    "DU123",
+                    "NetLiquidation",
+                    "100000.50",
+                    "USD",
+                ]),
+                frame(&["63", "1", &req_id, "DU456", "NetLiquidation", "5000", "USD"]),
+            ]
+        });
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            _ = async {
+                let tags = [AccountSummaryTag::NetLiquidation];
+                let mut states = client.req_account_summary_states("All", &tags).await.unwrap();
+                assert_eq!("DU123", states.next().await.unwrap().unwrap().account);
+                let state = states.next().await.unwrap().unwrap();
+                assert_eq!("DU456", state.account);
+                assert_eq!(Some(5000.0), state.net_liquidation());
+                let du123 = states.states().get("DU123").unwrap();
+                assert_eq!(Some(100000.5), du123.net_liquidation());
+            } => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn seeds_order_ids_from_next_valid_id() {
+        let gateway = MockGateway::start_with(versions::MAX_SERVER_VERSION, 5)
+            .await
+            .unwrap();
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        assert_eq!(5, client.next_order_id());
+
+        //TWS hands out ids past those placed by other clients
+        gateway.respond_with("8", vec![frame(&["9", "1", "100"])]);
+        gateway.respond("87", |request| {
+            let req_id = String::from_utf8_lossy(&request[1]).into_owned();
+            vec![frame(&["88", &req_id, "1618812000"])]
+        });
+        let contract = Contract::default();
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            //answered after NextValidId, so that was dispatched by then
+            _ = async {
+                client.req_ids().await.unwrap();
+                client.req_head_timestamp(&contract, HistoricalDataType::Trades, true).await.unwrap();
+            } => {}
+        }
+        assert_eq!(100, client.next_order_id());
+
+        //but never one we handed out already, asking for something else so
+        //pacing doesn't hold the request back
+        gateway.respond_with("8", vec![frame(&["9", "1", "50"])]);
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            _ = async {
+                client.req_ids().await.unwrap();
+                client.req_head_timestamp(&contract, HistoricalDataType::Midpoint, true).await.unwrap();
+            } => {}
+        }
+        assert_eq!(101, client.next_order_id());
+    }
+
+    #[tokio::test]
+    async fn dropping_a_subscription_cancels_it() {
+        let gateway = MockGateway::start().await.unwrap();
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            _ = async {
+                let market_data = client.req_market_data(Contract::default()).await.unwrap();
+                let id = market_data.id().to_string();
+                drop(market_data);
+                let cancel = gateway.expect("2").await.unwrap();
+                assert_eq!(id.as_bytes(), &cancel[2][..]);
+            } => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn newer_request_keeps_a_reserved_id() {
+        let gateway = MockGateway::start().await.unwrap();
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+        assert_eq!(&b"71"[..], &gateway.next_request().await.unwrap()[0][..]);
+
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            _ = async {
+                let old = client.req_positions().await.unwrap();
+                let new = client.req_positions().await.unwrap();
+                //cancels are handled in order, the old subscription's
+                //would come before the market data's
+                drop(old);
+                drop(client.req_market_data(Contract::default()).await.unwrap());
+                let mut ids = vec![];
+                for _ in 0..4 {
+                    let request = gateway.next_request().await.unwrap();
+                    ids.push(String::from_utf8_lossy(&request[0]).into_owned());
+                }
+                assert_eq!(vec!["61", "61", "1", "2"], ids);
+
+                drop(new);
+                gateway.expect("64").await.unwrap();
+            } => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn collects_historical_ticks_until_done() {
+        let gateway = MockGateway::start().await.unwrap();
+        gateway.respond("96", |request| {
+            let req_id = String::from_utf8_lossy(&request[1]).into_owned();
+            vec![
+                frame(&[
+                    "98",
+                    &req_id,
+                    "1",
+                    "1618847775",
+                    "0",
+                    "134.05",
+                    "100",
+                    "ARCA",
+                    "",
+                    "0",
+                ]),
+                frame(&[
+                    "98",
+                    &req_id,
+                    "1",
+                    "1618847776",
+                    "2",
+                    "134.06",
+                    "5",
+                    "NYSE",
+                    "I",
+                    "1",
+                ]),
+            ]
+        });
+        let client = ClientImpl::new(gateway.addr()).await.unwrap();
+
+        let contract = Contract::default();
+        tokio::select! {
+            res = client.run() => panic!("client stopped: {:?}", res),
+            ticks = client.historical_ticks(&contract, "20210419 15:56:15", "", 10, HistoricalDataType::Trades, true, false) => {
+                match ticks.unwrap() {
+                    HistoricalTicks::Last(ticks) => assert_eq!(
+                        vec![dec!(134.05), dec!(134.06)],
+                        ticks.iter().map(|t| t.price).collect::<Vec<_>>()
+                    ),
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_zero_rate_limit() {
+        let gateway = MockGateway::start().await.unwrap();
+        let rate_limit = RateLimit {
+            per_second: 0.0,
+            burst: 10,
+        };
+        assert!(matches!(
+            ClientImpl::with_rate_limit(gateway.addr(), rate_limit).await,
+            Err(Error::InvalidRateLimit { .. })
+        ));
+        assert_eq!(0, gateway.connections());
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_server_version() {
+        let gateway = MockGateway::start_with(versions::MIN_SERVER_VERSION - 1, 1)
+            .await
+            .unwrap();
+        assert!(matches!(
+            ClientImpl::new(gateway.addr()).await,
+            Err(Error::VersionMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use super::{
+    client::{Response, Subscription},
+    error::Error,
+    messages::{AccountSummaryTag, TWSIncommingMessageImpl},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountValue {
+    pub value: String,
+    pub currency: String,
+}
+
+/// The latest values of one account, fed from `AccountValue`,
+/// `AccountSummary` and `AccountUpdateMulti` alike since they share keys.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AccountState {
+    pub account: String,
+    //every key seen, including the per segment and per currency ones
+    pub values: HashMap<String, AccountValue>,
+}
+
+impl AccountState {
+    pub fn new(account: &str) -> AccountState {
+        AccountState {
+            account: account.to_owned(),
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, key: &str, value: &str, currency: &str) {
+        self.values.insert(
+            key.to_owned(),
+            AccountValue {
+                value: value.to_owned(),
+                currency: currency.to_owned(),
+            },
+        );
+    }
+
+    pub fn get(&self, tag: AccountSummaryTag) -> Option<&AccountValue> {
+        self.values.get(tag.as_str())
+    }
+
+    /// The numeric value of a tag, `None` if it's unknown or not a number.
+    pub fn amount(&self, tag: AccountSummaryTag) -> Option<f64> {
+        self.get(tag).and_then(|v| v.value.parse().ok())
+    }
+
+    pub fn net_liquidation(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::NetLiquidation)
+    }
+
+    pub fn buying_power(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::BuyingPower)
+    }
+
+    pub fn total_cash_value(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::TotalCashValue)
+    }
+
+    pub fn equity_with_loan_value(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::EquityWithLoanValue)
+    }
+
+    pub fn gross_position_value(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::GrossPositionValue)
+    }
+
+    pub fn available_funds(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::AvailableFunds)
+    }
+
+    pub fn excess_liquidity(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::ExcessLiquidity)
+    }
+
+    pub fn init_margin_req(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::InitMarginReq)
+    }
+
+    pub fn maint_margin_req(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::MaintMarginReq)
+    }
+
+    pub fn cushion(&self) -> Option<f64> {
+        self.amount(AccountSummaryTag::Cushion)
+    }
+}
+
+/// `AccountState`s by account.
+#[derive(Debug, Clone, Default)]
+pub struct AccountStates {
+    accounts: HashMap<String, AccountState>,
+}
+
+impl AccountStates {
+    pub fn new() -> AccountStates {
+        AccountStates::default()
+    }
+
+    pub fn get(&self, account: &str) -> Option<&AccountState> {
+        self.accounts.get(account)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AccountState> {
+        self.accounts.values()
+    }
+
+    /// Applies an account value message and returns the account it updated.
+    pub fn apply_message(&mut self, msg: &TWSIncommingMessageImpl) -> Option<&AccountState> {
+        let (account, key, value, currency) = match *msg {
+            TWSIncommingMessageImpl::AccountValue {
+                key,
+                value,
+                currency,
+                account_name,
+                ..
+            } => (account_name, key, value, currency),
+            TWSIncommingMessageImpl::AccountSummary {
+                account,
+                tag,
+                value,
+                currency,
+                ..
+            } => (account, tag, value, currency),
+            TWSIncommingMessageImpl::AccountUpdateMulti {
+                account,
+                key,
+                value,
+                currency,
+                ..
+            } => (account, key, value, currency),
+            _ => return None,
+        };
+        let state = self
+            .accounts
+            .entry(account.to_owned())
+            .or_insert_with(|| AccountState::new(account));
+        state.update(key, value, currency);
+        Some(state)
+    }
+}
+
+/// An account updates or summary subscription that maintains
+/// `AccountStates` and yields the account each message updated.
+#[derive(Debug)]
+pub struct AccountStatesSubscription {
+    subscription: Subscription,
+    states: AccountStates,
+}
+
+impl AccountStatesSubscription {
+    pub fn new(subscription: Subscription) -> AccountStatesSubscription {
+        AccountStatesSubscription {
+            subscription,
+            states: AccountStates::new(),
+        }
+    }
+
+    pub fn states(&self) -> &AccountStates {
+        &self.states
+    }
+
+    /// Waits for the next account value and returns the account with it
+    /// applied. Values are kept over a disconnect, TWS sends them again once
+    /// the request is replayed.
+    pub async fn next(&mut self) -> Option<Result<&AccountState, Error>> {
+        let account = loop {
+            match self.subscription.recv().await? {
+                Ok(Response::Message(msg)) => {
+                    if let Some(state) = self.states.apply_message(msg.get_msg()) {
+                        break state.account.clone();
+                    }
+                }
+                Ok(Response::Disconnected) | Ok(Response::Reconnected) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        };
+        self.states.get(&account).map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccountStates;
+    use crate::tws::messages::{AccountSummaryTag, TWSIncommingMessageImpl};
+
+    #[test]
+    fn aggregates_single_and_multi_account_values() {
+        let mut states = AccountStates::new();
+        states.apply_message(&TWSIncommingMessageImpl::AccountValue {
+            msg_version: 2,
+            key: "NetLiquidation",
+            value: "100000.50",
+            currency: "USD",
+            account_name: "DU123",
+        });
+        states.apply_message(&TWSIncommingMessageImpl::AccountSummary {
+            msg_version: 1,
+            req_id: 1,
+            account: "DU123",
+            tag: "BuyingPower",
+            value: "400000",
+            currency: "USD",
+        });
+        states.apply_message(&TWSIncommingMessageImpl::AccountUpdateMulti {
+            msg_version: 1,
+            req_id: 2,
+            account: "DU456",
+            model_code: "",
+            key: "SMA",
+            value: "12.5",
+            currency: "USD",
+        });
+
+        let du123 = states.get("DU123").unwrap();
+        assert_eq!(Some(100000.5), du123.net_liquidation());
+        assert_eq!(Some(400000.0), du123.buying_power());
+        assert_eq!(
+            Some(12.5),
+            states.get("DU456").unwrap().amount(AccountSummaryTag::Sma)
+        );
+        assert_eq!(2, states.iter().count());
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+
+use super::{
+    client::{Response, Subscription},
+    error::Error,
+    messages::{HistoricalBarData, TWSIncommingMessageImpl},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BarEvent {
+    /// The current bar changed, it may change again until it's closed.
+    Updated(HistoricalBarData),
+    /// A bar that won't change anymore.
+    Closed(HistoricalBarData),
+}
+
+/// The bars of a `keep_up_to_date` historical data request. The last bar is
+/// the one still forming: `HistoricalDataUpdate`s with its date amend it,
+/// one with a later date closes it and starts the next.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LiveBarSeries {
+    bars: Vec<HistoricalBarData>,
+    //date of the newest bar reported as closed
+    closed_until: Option<DateTime<Utc>>,
+}
+
+impl LiveBarSeries {
+    pub fn new() -> LiveBarSeries {
+        LiveBarSeries::default()
+    }
+
+    /// All bars, oldest first. The last one is still forming.
+    pub fn bars(&self) -> &[HistoricalBarData] {
+        &self.bars
+    }
+
+    pub fn current(&self) -> Option<&HistoricalBarData> {
+        self.bars.last()
+    }
+
+    /// Replaces the series with an initial batch of bars. Bars not closed
+    /// before are reported as closed, so a batch replayed after a reconnect
+    /// only reports what was missed.
+    pub fn load(&mut self, bars: &[HistoricalBarData]) -> Vec<BarEvent> {
+        self.bars = bars.to_vec();
+        let mut events = vec![];
+        if let Some((current, closed)) = self.bars.split_last() {
+            for bar in closed {
+                if self.closed_until.is_none_or(|d| bar.date > d) {
+                    events.push(BarEvent::Closed(bar.clone()));
+                }
+            }
+            events.push(BarEvent::Updated(current.clone()));
+        }
+        if let Some(bar) = self.bars.iter().rev().nth(1) {
+            self.closed_until = self.closed_until.max(Some(bar.date));
+        }
+        events
+    }
+
+    /// Amends the current bar or closes it and starts a new one. Updates for
+    /// bars older than the current one are ignored.
+    pub fn update(&mut self, bar: &HistoricalBarData) -> Vec<BarEvent> {
+        match self.bars.last_mut() {
+            Some(current) if bar.date < current.date => vec![],
+            Some(current) if bar.date == current.date => {
+                *current = bar.clone();
+                vec![BarEvent::Updated(bar.clone())]
+            }
+            Some(current) => {
+                let closed = current.clone();
+                self.closed_until = Some(closed.date);
+                self.bars.push(bar.clone());
+                vec![BarEvent::Closed(closed), BarEvent::Updated(bar.clone())]
+            }
+            None => {
+                self.bars.push(bar.clone());
+                vec![BarEvent::Updated(bar.clone())]
+            }
+        }
+    }
+
+    /// Applies a historical data message and returns the events it caused.
+    pub fn apply_message(&mut self, msg: &TWSIncommingMessageImpl) -> Vec<BarEvent> {
+        match msg {
+            TWSIncommingMessageImpl::HistoricalData { bars, .. } => self.load(bars),
+            TWSIncommingMessageImpl::HistoricalDataUpdate { bar, .. } => self.update(bar),
+            _ => vec![],
+        }
+    }
+}
+
+/// A `keep_up_to_date` historical data subscription that maintains a
+/// `LiveBarSeries` and yields its events.
+#[derive(Debug)]
+pub struct LiveBarSeriesSubscription {
+    subscription: Subscription,
+    series: LiveBarSeries,
+}
+
+impl LiveBarSeriesSubscription {
+    pub fn new(subscription: Subscription) -> LiveBarSeriesSubscription {
+        LiveBarSeriesSubscription {
+            subscription,
+            series: LiveBarSeries::new(),
+        }
+    }
+
+    pub fn series(&self) -> &LiveBarSeries {
+        &self.series
+    }
+
+    /// Waits for the next non-empty set of bar events. After a reconnect the
+    /// replayed request's bars fill in what was missed.
+    pub async fn next(&mut self) -> Option<Result<Vec<BarEvent>, Error>> {
+        loop {
+            match self.subscription.recv().await? {
+                Ok(Response::Message(msg)) => {
+                    let events = self.series.apply_message(msg.get_msg());
+                    if !events.is_empty() {
+                        return Some(Ok(events));
+                    }
+                }
+                Ok(Response::Disconnected) | Ok(Response::Reconnected) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Waits for the next closed bar, skipping updates of the current one.
+    pub async fn next_closed(&mut self) -> Option<Result<HistoricalBarData, Error>> {
+        loop {
+            match self.next().await? {
+                Ok(events) => {
+                    if let Some(BarEvent::Closed(bar)) = events
+                        .into_iter()
+                        .find(|e| matches!(e, BarEvent::Closed(_)))
+                    {
+                        return Some(Ok(bar));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BarEvent, LiveBarSeries};
+    use crate::tws::messages::HistoricalBarData;
+    use chrono::{Duration, TimeZone, Utc};
+    use fixed_macro::types::I44F20 as dec;
+
+    fn bar(minute: i64, close: i32) -> HistoricalBarData {
+        HistoricalBarData {
+            date: Utc.timestamp_opt(1618812000, 0).unwrap() + Duration::minutes(minute),
+            open: dec!(1),
+            high: dec!(2),
+            low: dec!(1),
+            close: close.into(),
+            volume: Some(10),
+            wap: None,
+            trade_count: None,
+        }
+    }
+
+    #[test]
+    fn amends_current_bar_and_closes_on_new_date() {
+        let mut series = LiveBarSeries::new();
+        assert_eq!(
+            vec![BarEvent::Closed(bar(0, 1)), BarEvent::Updated(bar(1, 1))],
+            series.load(&[bar(0, 1), bar(1, 1)])
+        );
+
+        assert_eq!(
+            vec![BarEvent::Updated(bar(1, 2))],
+            series.update(&bar(1, 2))
+        );
+        assert_eq!(
+            vec![BarEvent::Closed(bar(1, 2)), BarEvent::Updated(bar(2, 3))],
+            series.update(&bar(2, 3))
+        );
+        assert!(series.update(&bar(1, 5)).is_empty());
+        assert_eq!(3, series.bars().len());
+        assert_eq!(Some(&bar(2, 3)), series.current());
+    }
+
+    #[test]
+    fn reload_only_reports_missed_bars() {
+        let mut series = LiveBarSeries::new();
+        series.load(&[bar(0, 1), bar(1, 1)]);
+        series.update(&bar(2, 1));
+
+        assert_eq!(
+            vec![
+                BarEvent::Closed(bar(2, 1)),
+                BarEvent::Closed(bar(3, 1)),
+                BarEvent::Updated(bar(4, 1))
+            ],
+            series.load(&[bar(0, 1), bar(1, 1), bar(2, 1), bar(3, 1), bar(4, 1)])
+        );
+    }
+}
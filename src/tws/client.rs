@@ -1,24 +1,34 @@
 use bytes::{BufMut, BytesMut};
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
 use futures::{
-    stream::{SplitSink, SplitStream},
-    SinkExt,
+    stream::{FuturesUnordered, SplitSink, SplitStream},
+    SinkExt, Stream,
 };
 use std::fmt::Debug;
 use std::{
+    cmp::min,
     collections::HashMap,
-    error::Error,
-    sync::{self, atomic::AtomicI32},
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        self,
+        atomic::{AtomicI32, AtomicU64},
+    },
+    task::{Context, Poll},
+    time::Duration,
     u32,
 };
 use sync::atomic;
 use tokio::{
     io::AsyncWriteExt,
-    net::{TcpStream, ToSocketAddrs},
+    net::{lookup_host, TcpStream, ToSocketAddrs},
     sync::{
+        broadcast,
         mpsc::{UnboundedReceiver, UnboundedSender},
-        RwLock,
+        oneshot, Mutex, Notify, RwLock,
     },
+    time::Instant,
 };
 use tokio_util::codec::Framed;
 use twsapi::core::contract::Contract;
@@ -26,23 +36,305 @@ use twsapi::core::contract::Contract;
 use crate::tws::messages::BarSize::ValidBarSize;
 
 use super::{
+    account::AccountStatesSubscription,
+    bar_series::LiveBarSeriesSubscription,
     codec::{DecodedMessage, TWSCodec},
-    messages::{HistoricalDataType, TWSIncommingMessage, TWSOutgoingMessage},
+    error::{Error, Severity},
+    executions::{Fill, FillJoiner},
+    historical,
+    messages::{
+        AccountSummaryTag, Currency, DepthMktDataDescription, ExecutionFilter, HistoricalBarData,
+        HistoricalDataType, HistoricalTicks, Order, TWSIncommingMessage, TWSIncommingMessageImpl,
+        TWSOutgoingMessage, TickByTickType,
+    },
+    order_book::OrderBookSubscription,
+    orders::OrderSnapshot,
+    pacing::{Pacer, PacingKey, PacingStatus},
+    positions::PositionBookSubscription,
+    rate_limit::{RateLimit, ThrottleStats, TokenBucket},
+    recording::Recorder,
     serde::ser,
+    versions::{MAX_SERVER_VERSION, MIN_SERVER_VERSION},
 };
 use bytes::Bytes;
 use serde::Serialize;
 
 use tracing::{debug, error, info, instrument, trace, warn};
+
+type TWSSink = SplitSink<Framed<TcpStream, TWSCodec>, DecodedMessage>;
+type TWSStream = SplitStream<Framed<TcpStream, TWSCodec>>;
+
+const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+//TWS echoes request ids back in the same field as order ids (e.g. in errors),
+//so request ids start high enough to never meet an order id
+const REQ_ID_BASE: i32 = 1 << 30;
+const EVENTS_CAPACITY: usize = 64;
+const FILLS_CAPACITY: usize = 256;
+//reserved ids for the requests whose messages don't carry one, -1 is TWS' "no id"
+const POSITIONS_REQ_ID: i32 = -2;
+const ACCOUNT_UPDATES_REQ_ID: i32 = -3;
+
+/// What a request's channel receives: either a message routed to it by
+/// `req_id` or a notice that the connection to TWS dropped or was restored.
+/// Everything between a `Disconnected` and the following `Reconnected` is lost.
+/// A request-fatal TWS error arrives as an `Err` and ends the channel.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Response {
+    Message(TWSIncommingMessage),
+    Disconnected,
+    Reconnected,
+}
+
+/// Client-wide notices, see `ClientImpl::events`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The socket to TWS closed, the client is reconnecting.
+    Disconnected,
+    Reconnected,
+    /// TWS reported a connection-level error like 1100 (connectivity between
+    /// TWS and IB lost) or 1102 (restored, data maintained).
+    Connection {
+        code: i32,
+        message: String,
+    },
+}
+
+type ResponseSender = UnboundedSender<Result<Response, Error>>;
+type ResponseReceiver = UnboundedReceiver<Result<Response, Error>>;
+
+#[derive(Debug)]
+struct Handler {
+    sender: ResponseSender,
+    //the encoded request, re-sent as is after a reconnect
+    request: DecodedMessage,
+    //sent when the subscription is dropped before it finished
+    cancel: Option<DecodedMessage>,
+    is_done: fn(&TWSIncommingMessageImpl) -> bool,
+    //historical requests are paced again when re-sent
+    pacing: Option<PacingKey>,
+}
+
+/// The receiving end of a request. Dropping it before the request finished
+/// cancels the request with TWS and unregisters it from the client.
+#[derive(Debug)]
+pub struct Subscription {
+    id: i32,
+    receiver: ResponseReceiver,
+    cancels: UnboundedSender<i32>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub async fn recv(&mut self) -> Option<Result<Response, Error>> {
+        self.receiver.recv().await
+    }
+
+    /// Cancels the request, same as dropping it.
+    pub fn cancel(self) {}
+}
+
+impl Stream for Subscription {
+    type Item = Result<Response, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        //closed first so `cancel_request` can tell us from a newer request
+        //that took over a reserved id
+        self.receiver.close();
+        //fails only once the client is gone, and with it the request
+        let _ = self.cancels.send(self.id);
+    }
+}
+
+/// The updates of a placed order. Dropping it only unregisters the order
+/// from the client, the order itself stays live with TWS.
+#[derive(Debug)]
+pub struct OrderSubscription {
+    order_id: i32,
+    receiver: ResponseReceiver,
+    cancels: UnboundedSender<i32>,
+}
+
+impl OrderSubscription {
+    pub fn order_id(&self) -> i32 {
+        self.order_id
+    }
+
+    pub async fn recv(&mut self) -> Option<Result<Response, Error>> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for OrderSubscription {
+    type Item = Result<Response, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for OrderSubscription {
+    fn drop(&mut self) {
+        self.receiver.close();
+        let _ = self.cancels.send(self.order_id);
+    }
+}
+
+fn never_done(_: &TWSIncommingMessageImpl) -> bool {
+    false
+}
+
+//waits for the message a single reply request resolves to, connection notices
+//are skipped since the request is replayed after a reconnect
+async fn first_message<T>(
+    mut subscription: Subscription,
+    what: &str,
+    extract: impl Fn(&TWSIncommingMessageImpl) -> Option<T>,
+) -> Result<T, Error> {
+    while let Some(response) = subscription.recv().await {
+        if let Response::Message(msg) = response? {
+            if let Some(value) = extract(msg.get_msg()) {
+                return Ok(value);
+            }
+        }
+    }
+    Err(Error::Io(std::io::Error::new(
+        std::io::ErrorKind::ConnectionAborted,
+        format!("client stopped before {} arrived", what),
+    )))
+}
+
+fn implicit_req_id(msg: &TWSIncommingMessageImpl) -> Option<i32> {
+    match msg {
+        TWSIncommingMessageImpl::Position { .. } | TWSIncommingMessageImpl::PositionEnd { .. } => {
+            Some(POSITIONS_REQ_ID)
+        }
+        TWSIncommingMessageImpl::AccountValue { .. }
+        | TWSIncommingMessageImpl::PortfolioValue { .. }
+        | TWSIncommingMessageImpl::AccountUpdateTime { .. }
+        | TWSIncommingMessageImpl::AccountDownloadEnd { .. } => Some(ACCOUNT_UPDATES_REQ_ID),
+        _ => None,
+    }
+}
+
+/// The order or req_id `ClientImpl` routes a message to, if any.
+pub fn route_id(msg: &TWSIncommingMessageImpl) -> Option<i32> {
+    msg.get_order_id()
+        .or_else(|| msg.get_req_id())
+        .or_else(|| implicit_req_id(msg))
+}
+
+//withdraws a request from the pacing queue if it's dropped while waiting,
+//and wakes those queued behind it
+struct PacingTicket<'a> {
+    pacer: &'a sync::Mutex<Pacer>,
+    paced: &'a Notify,
+    ticket: u64,
+}
+
+impl Drop for PacingTicket<'_> {
+    fn drop(&mut self) {
+        self.pacer.lock().unwrap().withdraw(self.ticket);
+        self.paced.notify_waiters();
+    }
+}
+
+fn pacing_key(contract: &Contract, data_type: impl Debug, params: String) -> PacingKey {
+    let contract = format!(
+        "{}|{}|{}|{}|{}|{}|{}|{}|{}|{:?}",
+        contract.con_id,
+        contract.symbol,
+        contract.sec_type,
+        contract.last_trade_date_or_contract_month,
+        contract.strike,
+        contract.right,
+        contract.multiplier,
+        contract.exchange,
+        contract.currency,
+        data_type
+    );
+    PacingKey {
+        request: format!("{}|{}", contract, params),
+        contract,
+    }
+}
+
+#[derive(Debug)]
+struct OrderCollector {
+    orders: Vec<OrderSnapshot>,
+    done: oneshot::Sender<Result<Vec<OrderSnapshot>, Error>>,
+    //messages up to this one were read before the request was sent
+    after: u64,
+}
+
+impl OrderCollector {
+    //TWS may report an order more than once, e.g. when it's also placed or
+    //modified during the collection, keep the latest
+    fn push(&mut self, order: OrderSnapshot) {
+        let perm_id = order.order.perm_id;
+        match self
+            .orders
+            .iter_mut()
+            .find(|o| perm_id != 0 && o.order.perm_id == perm_id)
+        {
+            Some(o) => *o = order,
+            None => self.orders.push(order),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ClientImpl {
     //framed: Framed<TcpStream, TWSCodec>,
-    stream: RwLock<SplitStream<Framed<TcpStream, TWSCodec>>>,
-    sink: RwLock<SplitSink<Framed<TcpStream, TWSCodec>, DecodedMessage>>,
+    addrs: Vec<SocketAddr>,
+    stream: RwLock<TWSStream>,
+    sink: RwLock<TWSSink>,
     //receiver: UnboundedReceiver<DecodedMessage>,
     //sender: UnboundedSender<DecodedMessage>,
     req_id: AtomicI32,
-    map: RwLock<HashMap<i32, UnboundedSender<TWSIncommingMessage>>>,
+    order_id: AtomicI32,
+    map: RwLock<HashMap<i32, Handler>>,
+    //ids of dropped subscriptions, handled by `run`
+    cancels: UnboundedSender<i32>,
+    cancelled: RwLock<UnboundedReceiver<i32>>,
+    //orders are not replayed on reconnect, TWS keeps them alive on its own
+    orders: RwLock<HashMap<i32, ResponseSender>>,
+    events: broadcast::Sender<Event>,
+    //commission reports carry no id, so every fill goes to everyone
+    fills: broadcast::Sender<Fill>,
+    fill_joiner: RwLock<FillJoiner>,
+    //MktDepthExchanges carries no req_id, so everyone waiting gets the next one
+    depth_exchanges: RwLock<Vec<oneshot::Sender<Vec<DepthMktDataDescription>>>>,
+    //same for open and completed orders, collected until their end marker.
+    //One collection of each runs at a time, waiting for its turn and for an
+    //abandoned one before it to end, `collected` is notified when one does
+    open_orders: RwLock<Option<OrderCollector>>,
+    completed_orders: RwLock<Option<OrderCollector>>,
+    open_orders_turn: Mutex<()>,
+    completed_orders_turn: Mutex<()>,
+    collected: Notify,
+    //number of messages dispatched so far
+    received: AtomicU64,
+    //historical requests wait for those queued before them for the same
+    //contract, `paced` is notified when one leaves the queue
+    pacer: sync::Mutex<Pacer>,
+    paced: Notify,
+    //taken while holding `sink`, so messages leave in the order they got it
+    limiter: sync::Mutex<TokenBucket>,
+    //negotiated again on every reconnect
+    server_version: AtomicI32,
+    connection_time: sync::Mutex<String>,
+    recorder: Option<Recorder>,
 }
 use std::fmt;
 /* impl Debug for ClientImpl {
@@ -75,122 +367,752 @@ fn make_message(contract: &Contract) -> DecodedMessage {
     ]
 }
 
+fn encode(msg: &TWSOutgoingMessage) -> Result<DecodedMessage, Error> {
+    let mut buf = Vec::with_capacity(1024);
+    ser::to_writer(msg, &mut buf)?;
+    Ok(vec![Bytes::from(buf)])
+}
+
+struct Connection {
+    sink: TWSSink,
+    stream: TWSStream,
+    order_id: i32,
+    server_version: i32,
+    connection_time: String,
+}
+
+/// Connects to the first reachable address, negotiates a server version
+/// within `MIN_SERVER_VERSION..MAX_SERVER_VERSION`, sends `StartApi` and waits
+/// for the server's next valid order id.
+#[instrument]
+async fn handshake(addrs: &[SocketAddr], recorder: Option<&Recorder>) -> Result<Connection, Error> {
+    let tcp = TcpStream::connect(addrs).await?;
+    let codec = match recorder {
+        Some(recorder) => TWSCodec::with_recorder(recorder.clone()),
+        None => TWSCodec::new(),
+    };
+    let mut framed = Framed::new(tcp, codec);
+    let mut bytes = BytesMut::with_capacity(20);
+    bytes.put(&b"API\0"[..]);
+    let version = format!("v{}..{}", MIN_SERVER_VERSION, MAX_SERVER_VERSION);
+    bytes.put_u32(version.len() as u32);
+    bytes.put(version.as_bytes());
+    debug!(?bytes, "Writing init bytes");
+    framed.get_mut().write_all(&bytes[..]).await?;
+
+    //the server answers with the version it picked and its connection time
+    let (server_version, connection_time) = match framed.next().await {
+        Some(Ok(msg)) => {
+            let field = |i: usize| {
+                msg.get(i)
+                    .map(|b| String::from_utf8_lossy(b).into_owned())
+                    .unwrap_or_default()
+            };
+            match field(0).parse::<i32>() {
+                Ok(v) if (MIN_SERVER_VERSION..=MAX_SERVER_VERSION).contains(&v) => {
+                    framed
+                        .send(vec![
+                            "71".into(),
+                            "2".into(),
+                            "0".into(),
+                            Bytes::new(),
+                            Bytes::new(),
+                        ])
+                        .await?;
+                    (v, field(1))
+                }
+                _ => {
+                    return Err(Error::VersionMismatch {
+                        min: MIN_SERVER_VERSION,
+                        max: MAX_SERVER_VERSION,
+                        actual: field(0),
+                    })
+                }
+            }
+        }
+        Some(Err(e)) => {
+            error!("Got error: {:?}", e);
+            return Err(e);
+        }
+        None => {
+            return Err(Error::Handshake(
+                "connection closed during handshake".to_owned(),
+            ))
+        }
+    };
+    info!(server_version, %connection_time, "Negotiated server version");
+
+    let order_id = loop {
+        match framed.next().await {
+            Some(Ok(msg)) => {
+                let body =
+                    TWSIncommingMessage::from_decoded_message_with_version(msg, server_version)?;
+                match body.get_msg() {
+                    TWSIncommingMessageImpl::NextValidId { order_id, .. } => break *order_id,
+                    msg => debug!(?msg, "Got msg before next valid id"),
+                }
+            }
+            Some(Err(e)) => {
+                error!("Got error: {:?}", e);
+                return Err(e);
+            }
+            None => {
+                return Err(Error::Handshake(
+                    "connection closed before next valid id".to_owned(),
+                ))
+            }
+        }
+    };
+
+    let (sink, stream) = framed.split();
+    Ok(Connection {
+        sink,
+        stream,
+        order_id,
+        server_version,
+        connection_time,
+    })
+}
+
 impl ClientImpl {
     #[instrument]
-    pub async fn new<A: ToSocketAddrs + Debug>(addr: A) -> Result<ClientImpl, Box<dyn Error>> {
+    pub async fn new<A: ToSocketAddrs + Debug>(addr: A) -> Result<ClientImpl, Error> {
+        ClientImpl::with_rate_limit(addr, RateLimit::default()).await
+    }
+
+    /// Like `new` but with a custom limit on the messages sent to TWS, see
+    /// `RateLimit::validate`.
+    #[instrument]
+    pub async fn with_rate_limit<A: ToSocketAddrs + Debug>(
+        addr: A,
+        rate_limit: RateLimit,
+    ) -> Result<ClientImpl, Error> {
+        ClientImpl::connect(addr, rate_limit, None).await
+    }
+
+    /// Like `new` but records every frame sent and received, including
+    /// those of later reconnects, see `recording::Replayer`.
+    #[instrument]
+    pub async fn with_recorder<A: ToSocketAddrs + Debug>(
+        addr: A,
+        recorder: Recorder,
+    ) -> Result<ClientImpl, Error> {
+        ClientImpl::connect(addr, RateLimit::default(), Some(recorder)).await
+    }
+
+    async fn connect<A: ToSocketAddrs + Debug>(
+        addr: A,
+        rate_limit: RateLimit,
+        recorder: Option<Recorder>,
+    ) -> Result<ClientImpl, Error> {
         debug!("Creating new client");
-        let tcp = TcpStream::connect(addr).await?;
-        let mut framed = Framed::new(tcp, TWSCodec::new());
-        let mut bytes = BytesMut::with_capacity(20);
-        bytes.put(&b"API\0"[..]);
-        const VERSION: &[u8] = b"v151..151";
-        bytes.put_u32(VERSION.len() as u32);
-        bytes.put(&VERSION[..]);
-        debug!(?bytes, "Writing init bytes");
-        framed.get_mut().write_all(&bytes[..]).await?;
-
-        let (sink, stream) = framed.split();
-        let c = ClientImpl {
+        rate_limit.validate()?;
+        let addrs = lookup_host(addr).await?.collect::<Vec<_>>();
+        let Connection {
+            sink,
+            stream,
+            order_id,
+            server_version,
+            connection_time,
+        } = handshake(&addrs, recorder.as_ref()).await?;
+        info!(order_id, "Got next valid order id");
+        let (cancels, cancelled) = tokio::sync::mpsc::unbounded_channel();
+
+        Ok(ClientImpl {
+            addrs,
             sink: RwLock::new(sink),
             stream: RwLock::new(stream),
-            req_id: AtomicI32::default(),
+            req_id: AtomicI32::new(REQ_ID_BASE),
+            order_id: AtomicI32::new(order_id),
             map: RwLock::default(),
-        };
+            cancels,
+            cancelled: RwLock::new(cancelled),
+            orders: RwLock::default(),
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            fills: broadcast::channel(FILLS_CAPACITY).0,
+            fill_joiner: RwLock::default(),
+            depth_exchanges: RwLock::default(),
+            open_orders: RwLock::default(),
+            completed_orders: RwLock::default(),
+            open_orders_turn: Mutex::default(),
+            collected: Notify::new(),
+            completed_orders_turn: Mutex::default(),
+            received: AtomicU64::new(0),
+            pacer: sync::Mutex::default(),
+            paced: Notify::new(),
+            limiter: sync::Mutex::new(TokenBucket::new(rate_limit, Instant::now())),
+            server_version: AtomicI32::new(server_version),
+            connection_time: sync::Mutex::new(connection_time),
+            recorder,
+        })
+    }
 
-        let ok = {
-            let mut stream = c.stream.write().await;
-
-            loop {
-                if let Some(res) = stream.next().await {
-                    match res {
-                        Ok(msg) => {
-                            if let Some(b) = msg.first() {
-                                if *b == "151" {
-                                    let _ = c
-                                        .sink
-                                        .write()
-                                        .await
-                                        .send(vec![
-                                            "71".into(),
-                                            "2".into(),
-                                            "0".into(),
-                                            Bytes::new(),
-                                            Bytes::new(),
-                                        ])
-                                        .await;
-
-                                    break true;
+    /// Routes incoming messages to their requests and cancels dropped
+    /// subscriptions. When the connection drops every request is told so, the
+    /// client reconnects with backoff, re-issues all live requests under their
+    /// original ids and tells them it is back.
+    #[instrument(skip(self))]
+    pub async fn run(&self) -> Result<(), Error> {
+        info!("Started client loop");
+        //historical requests re-sent once pacing allows, alongside reading
+        let mut replays = FuturesUnordered::new();
+        loop {
+            {
+                let mut stream = self.stream.write().await;
+                let mut cancelled = self.cancelled.write().await;
+                loop {
+                    tokio::select! {
+                        res = stream.next() => match res {
+                            Some(Ok(msg)) => {
+                                match TWSIncommingMessage::try_from_frame(msg, self.server_version()) {
+                                    Ok(msg) => self.dispatch(msg).await,
+                                    //one message we can't read shouldn't end the session
+                                    Err((e, frame)) => {
+                                        error!(?e, payload = ?frame.payload(), "Skipping undecodable message")
+                                    }
                                 }
                             }
-                        }
-                        Err(e) => {
-                            error!("Got error: {:?}", e);
-                            break false;
-                        }
+                            Some(Err(e)) => error!(?e, "Got error during streaming"),
+                            None => break,
+                        },
+                        Some(id) = cancelled.recv() => self.cancel_request(id).await,
+                        Some(()) = replays.next() => {}
                     }
                 }
             }
-        };
-        return if ok { Ok(c) } else { Err("foobar".into()) };
+
+            warn!("Lost connection to TWS");
+            let _ = self.events.send(Event::Disconnected);
+            self.notify_all(|| Response::Disconnected).await;
+            self.abort_collections().await;
+            replays.clear();
+            for (id, pacing) in self.reconnect().await {
+                replays.push(self.replay_paced(id, pacing));
+            }
+            self.notify_all(|| Response::Reconnected).await;
+            let _ = self.events.send(Event::Reconnected);
+        }
+    }
+
+    //never hand out an id twice, even if TWS reports a lower one than ours
+    fn seed_order_id(&self, order_id: i32) {
+        let prev = self.order_id.fetch_max(order_id, atomic::Ordering::Relaxed);
+        debug!(prev, order_id, "Seeded next order id");
+    }
+
+    /// Reserves the next order id, for use with `place_order`.
+    pub fn next_order_id(&self) -> i32 {
+        self.order_id.fetch_add(1, atomic::Ordering::Relaxed)
     }
 
+    /// Asks TWS for the next valid order id and resyncs the order id
+    /// allocator with it once it arrives, e.g. after orders were placed by
+    /// another client.
     #[instrument(skip(self))]
-    pub async fn run(&self) -> Result<(), Box<dyn Error>> {
-        info!("Started client loop");
-        while let Some(res) = self.stream.write().await.next().await {
-            match res {
-                Ok(msg) => {
-                    let body = TWSIncommingMessage::from_decoded_message(msg)?;
-                    let msg = body.get_msg();
-                    if let Some(req_id) = msg.get_req_id() {
-                        if let Some(s) = self.map.read().await.get(&req_id) {
-                            s.send(body);
-                        } else {
-                            warn!(?msg, "Got msg without handler");
-                        }
+    pub async fn req_ids(&self) -> Result<(), Error> {
+        let msg = TWSOutgoingMessage::RequestIds {
+            version: 1,
+            num_ids: 1,
+        };
+        self.send(encode(&msg)?).await?;
+        Ok(())
+    }
+
+    async fn dispatch_order(&self, order_id: i32, body: TWSIncommingMessage) {
+        let mut orders = self.orders.write().await;
+        match orders.get(&order_id) {
+            Some(sender) => {
+                let done = matches!(
+                    body.get_msg(),
+                    TWSIncommingMessageImpl::OrderStatus { status, .. } if status.is_done()
+                );
+                let _ = sender.send(Ok(Response::Message(body)));
+                if done {
+                    orders.remove(&order_id);
+                }
+            }
+            None => trace!(order_id, "Got update for unknown order"),
+        }
+    }
+
+    /// Subscribes to connection-level events. Events sent while nobody
+    /// listens are dropped, a receiver lagging far behind skips the oldest.
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// How many historical requests are held back to respect IB's pacing
+    /// rules and how long until the last of them is sent.
+    pub fn pacing_status(&self) -> PacingStatus {
+        self.pacer.lock().unwrap().status(Instant::now())
+    }
+
+    //holds a historical data, histogram, head timestamp or historical ticks
+    //request back until sending it keeps within IB's pacing rules
+    async fn pace(&self, key: PacingKey) {
+        let ticket = self.pacer.lock().unwrap().enqueue(key.clone());
+        let _ticket = PacingTicket {
+            pacer: &self.pacer,
+            paced: &self.paced,
+            ticket,
+        };
+        loop {
+            let paced = self.paced.notified();
+            tokio::pin!(paced);
+            paced.as_mut().enable();
+            let now = Instant::now();
+            let at = {
+                let mut pacer = self.pacer.lock().unwrap();
+                if pacer.is_behind(ticket, &key) {
+                    None
+                } else {
+                    let at = pacer.next_slot(now, &key);
+                    if at <= now {
+                        pacer.release(ticket, now);
+                    }
+                    Some(at)
+                }
+            };
+            match at {
+                Some(at) if at <= now => break,
+                //others may take the slot meanwhile, so look again after
+                Some(at) => {
+                    debug!(wait = ?(at - now), "Pacing historical request");
+                    tokio::time::sleep_until(at).await;
+                }
+                None => paced.await,
+            }
+        }
+    }
+
+    //feeds `open_orders` and `completed_orders`
+    async fn collect_orders(&self, msg: &TWSIncommingMessageImpl<'_>) {
+        let collectors = match msg {
+            TWSIncommingMessageImpl::OpenOrder { .. }
+            | TWSIncommingMessageImpl::OpenOrderEnd { .. } => &self.open_orders,
+            TWSIncommingMessageImpl::CompletedOrder { .. }
+            | TWSIncommingMessageImpl::CompletedOrdersEnd => &self.completed_orders,
+            _ => return,
+        };
+        let seq = self.received.load(atomic::Ordering::Relaxed);
+        let mut collector = collectors.write().await;
+        if collector.as_ref().is_none_or(|c| seq <= c.after) {
+            return;
+        }
+        match OrderSnapshot::from_message(msg) {
+            Some(order) => collector.as_mut().unwrap().push(order),
+            None => {
+                let c = collector.take().unwrap();
+                let _ = c.done.send(Ok(c.orders));
+                self.collected.notify_waiters();
+            }
+        }
+    }
+
+    //the answers to collections in flight are lost with the connection
+    async fn abort_collections(&self) {
+        for collectors in [&self.open_orders, &self.completed_orders] {
+            if let Some(c) = collectors.write().await.take() {
+                let _ = c.done.send(Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionAborted,
+                    "lost connection while collecting orders",
+                ))));
+            }
+        }
+        self.collected.notify_waiters();
+    }
+
+    //sends `msg` and collects the orders TWS answers with until their end
+    //marker. Only messages dispatched after the request was sent count, like
+    //the end marker TWS sends on connect or after `req_auto_open_orders`.
+    //A collection whose caller went away stays until its end marker, so that
+    //one can't end the next collection early
+    async fn collect(
+        &self,
+        collector: &RwLock<Option<OrderCollector>>,
+        turn: &Mutex<()>,
+        msg: &TWSOutgoingMessage,
+        what: &str,
+    ) -> Result<Vec<OrderSnapshot>, Error> {
+        let _turn = turn.lock().await;
+        let (done, receiver) = oneshot::channel();
+        let request = encode(msg)?;
+        {
+            //messages dispatched while the request goes out wait for the
+            //collector and are then ignored as read before it
+            let mut collector = loop {
+                let collected = self.collected.notified();
+                tokio::pin!(collected);
+                collected.as_mut().enable();
+                let collector = collector.write().await;
+                if collector.is_none() {
+                    break collector;
+                }
+                drop(collector);
+                debug!("Waiting for an abandoned {} request to end", what);
+                collected.await;
+            };
+            self.send(request).await?;
+            *collector = Some(OrderCollector {
+                orders: vec![],
+                done,
+                after: self.received.load(atomic::Ordering::Relaxed),
+            });
+        }
+
+        receiver.await.map_err(|_| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionAborted,
+                format!("client stopped before {} arrived", what),
+            ))
+        })?
+    }
+
+    /// Subscribes to fills, both live ones and those of executions replayed
+    /// by `req_executions`, so the same fill may arrive more than once.
+    pub fn fills(&self) -> broadcast::Receiver<Fill> {
+        self.fills.subscribe()
+    }
+
+    //handles errors that shouldn't reach a request as a message, returns
+    //whether it did
+    async fn dispatch_error(&self, error: Error) -> bool {
+        let (req_id, code, text) = match &error {
+            Error::Tws {
+                req_id,
+                code,
+                message,
+            } => (*req_id, *code, message.as_str()),
+            _ => return false,
+        };
+        let severity = Severity::of(code);
+        match severity {
+            Severity::Connection => {
+                warn!(req_id, code, text, "Connection state changed");
+                let _ = self.events.send(Event::Connection {
+                    code,
+                    message: text.to_owned(),
+                });
+            }
+            _ if req_id == -1 => match severity {
+                Severity::Info => info!(code, text, "TWS notice"),
+                Severity::Warning => warn!(code, text, "TWS warning"),
+                _ => error!(code, text, "TWS error"),
+            },
+            Severity::RequestFatal => {
+                if let Some(h) = self.map.write().await.remove(&req_id) {
+                    let _ = h.sender.send(Err(error));
+                } else if let Some(sender) = self.orders.write().await.remove(&req_id) {
+                    let _ = sender.send(Err(error));
+                } else {
+                    warn!(%error, "Got error without handler");
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    async fn dispatch(&self, body: TWSIncommingMessage) {
+        self.received.fetch_add(1, atomic::Ordering::Relaxed);
+        let msg = body.get_msg();
+        if let Some(error) = msg.to_error() {
+            if self.dispatch_error(error).await {
+                return;
+            }
+        }
+
+        if let Some(fill) = self.fill_joiner.write().await.apply_message(msg) {
+            let _ = self.fills.send(fill);
+        }
+        self.collect_orders(msg).await;
+
+        if let TWSIncommingMessageImpl::NextValidId { order_id, .. } = msg {
+            self.seed_order_id(*order_id);
+        } else if let TWSIncommingMessageImpl::MktDepthExchanges { descriptions } = msg {
+            for waiter in self.depth_exchanges.write().await.drain(..) {
+                let _ = waiter.send(descriptions.clone());
+            }
+        } else if let Some(order_id) = msg.get_order_id() {
+            self.dispatch_order(order_id, body).await;
+        } else if let Some(req_id) = msg.get_req_id().or_else(|| implicit_req_id(msg)) {
+            let done = match self.map.read().await.get(&req_id) {
+                Some(h) => {
+                    let done = (h.is_done)(msg);
+                    let _ = h.sender.send(Ok(Response::Message(body)));
+                    done
+                }
+                None => {
+                    if self.orders.read().await.contains_key(&req_id) {
+                        //errors about an order carry the order id as req_id
+                        self.dispatch_order(req_id, body).await;
                     } else {
-                        trace!(?msg, "Got req without id");
+                        warn!(?msg, "Got msg without handler");
                     }
+                    false
                 }
-                Err(e) => error!(?e, "Got error during streaming"),
+            };
+            if done {
+                debug!(req_id, "Request finished");
+                self.map.write().await.remove(&req_id);
             }
+        } else {
+            trace!(?msg, "Got req without id");
         }
-        Ok(())
     }
 
+    async fn cancel_request(&self, id: i32) {
+        let handler = {
+            let mut map = self.map.write().await;
+            match map.get(&id) {
+                Some(h) if h.sender.is_closed() => map.remove(&id),
+                _ => None,
+            }
+        };
+        //orders are only unregistered, they stay live with TWS
+        {
+            let mut orders = self.orders.write().await;
+            if orders.get(&id).is_some_and(|sender| sender.is_closed()) {
+                orders.remove(&id);
+            }
+        }
+        if let Some(Handler {
+            cancel: Some(cancel),
+            ..
+        }) = handler
+        {
+            debug!(id, "Cancelling req");
+            if let Err(e) = self.send(cancel).await {
+                error!(?e, id, "Failed to cancel req");
+            }
+        }
+    }
+
+    async fn notify_all(&self, event: impl Fn() -> Response) {
+        for h in self.map.read().await.values() {
+            let _ = h.sender.send(Ok(event()));
+        }
+        for sender in self.orders.read().await.values() {
+            let _ = sender.send(Ok(event()));
+        }
+    }
+
+    //returns the historical requests still to be re-sent
+    async fn reconnect(&self) -> Vec<(i32, PacingKey)> {
+        let mut delay = RECONNECT_MIN_DELAY;
+        loop {
+            tokio::time::sleep(delay).await;
+            info!(?delay, "Reconnecting to TWS");
+
+            match handshake(&self.addrs, self.recorder.as_ref()).await {
+                Ok(connection) => {
+                    self.seed_order_id(connection.order_id);
+                    self.server_version
+                        .store(connection.server_version, atomic::Ordering::Relaxed);
+                    *self.connection_time.lock().unwrap() = connection.connection_time;
+                    *self.sink.write().await = connection.sink;
+                    *self.stream.write().await = connection.stream;
+                    return match self.replay().await {
+                        Ok((n, paced)) => {
+                            info!(n, paced = paced.len(), "Reconnected and replayed requests");
+                            paced
+                        }
+                        //the stream will end right away and we go around again
+                        Err(e) => {
+                            error!(?e, "Failed to replay requests");
+                            vec![]
+                        }
+                    };
+                }
+                Err(e) => warn!(%e, "Failed to reconnect"),
+            }
+            delay = min(delay * 2, RECONNECT_MAX_DELAY);
+        }
+    }
+
+    //re-sends the requests that aren't paced, returns how many and the
+    //paced ones
+    async fn replay(&self) -> Result<(usize, Vec<(i32, PacingKey)>), Error> {
+        let map = self.map.read().await;
+        let mut sink = self.sink.write().await;
+        let mut paced = vec![];
+        for (id, h) in map.iter() {
+            if let Some(pacing) = &h.pacing {
+                paced.push((*id, pacing.clone()));
+                continue;
+            }
+            debug!(id, "Replaying req");
+            self.throttle().await;
+            sink.send(h.request.clone()).await?;
+        }
+        Ok((map.len() - paced.len(), paced))
+    }
+
+    //re-sends a historical request once pacing allows, unless it was
+    //cancelled meanwhile
+    async fn replay_paced(&self, id: i32, pacing: PacingKey) {
+        self.pace(pacing).await;
+        let request = self.map.read().await.get(&id).map(|h| h.request.clone());
+        if let Some(request) = request {
+            debug!(id, "Replaying paced req");
+            if let Err(e) = self.send(request).await {
+                error!(?e, id, "Failed to replay req");
+            }
+        }
+    }
+
+    //waits until the rate limit allows another message, callers hold the sink
+    //meanwhile
+    async fn throttle(&self) {
+        let wait = self.limiter.lock().unwrap().reserve(Instant::now());
+        if !wait.is_zero() {
+            trace!(?wait, "Throttling outgoing message");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn send(&self, msg: DecodedMessage) -> Result<(), Error> {
+        let mut sink = self.sink.write().await;
+        self.throttle().await;
+        sink.send(msg).await
+    }
+
+    /// How many messages were sent to TWS and how long they were held back
+    /// by the rate limit.
+    pub fn throttle_stats(&self) -> ThrottleStats {
+        self.limiter.lock().unwrap().stats()
+    }
+
+    /// The server version negotiated with TWS.
+    pub fn server_version(&self) -> i32 {
+        self.server_version.load(atomic::Ordering::Relaxed)
+    }
+
+    /// The connection time TWS reported during the handshake, in its own
+    /// format and time zone, e.g. `20210309 22:54:30 CET`.
+    pub fn connection_time(&self) -> String {
+        self.connection_time.lock().unwrap().clone()
+    }
+
+    async fn send_request(
+        &self,
+        id: i32,
+        msg: &TWSOutgoingMessage,
+        cancel: Option<TWSOutgoingMessage>,
+        is_done: fn(&TWSIncommingMessageImpl) -> bool,
+    ) -> Result<Subscription, Error> {
+        self.register_request(id, msg, cancel, is_done, None).await
+    }
+
+    //like `send_request` once `pace` lets the request go
+    async fn send_paced_request(
+        &self,
+        id: i32,
+        pacing: PacingKey,
+        msg: &TWSOutgoingMessage,
+        cancel: Option<TWSOutgoingMessage>,
+        is_done: fn(&TWSIncommingMessageImpl) -> bool,
+    ) -> Result<Subscription, Error> {
+        self.pace(pacing.clone()).await;
+        self.register_request(id, msg, cancel, is_done, Some(pacing))
+            .await
+    }
+
+    async fn register_request(
+        &self,
+        id: i32,
+        msg: &TWSOutgoingMessage,
+        cancel: Option<TWSOutgoingMessage>,
+        is_done: fn(&TWSIncommingMessageImpl) -> bool,
+        pacing: Option<PacingKey>,
+    ) -> Result<Subscription, Error> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let request = encode(msg)?;
+        let cancel = cancel.as_ref().map(encode).transpose()?;
+
+        self.map.write().await.insert(
+            id,
+            Handler {
+                sender,
+                request: request.clone(),
+                cancel,
+                is_done,
+                pacing,
+            },
+        );
+        self.send(request).await?;
+
+        Ok(Subscription {
+            id,
+            receiver,
+            cancels: self.cancels.clone(),
+        })
+    }
+
+    /// Places an order under the next order id. The subscription gets its
+    /// `OpenOrder`, `OrderStatus` and `Error` messages and is closed once the
+    /// order is filled, cancelled, inactive or rejected.
     #[instrument(skip(self))]
-    pub async fn req_market_data(
+    pub async fn place_order(
         &self,
-        contract: Contract,
-    ) -> Result<UnboundedReceiver<TWSIncommingMessage>, Box<dyn Error + '_>> {
-        let (s, r) = tokio::sync::mpsc::unbounded_channel();
+        contract: &Contract,
+        order: &Order,
+    ) -> Result<OrderSubscription, Error> {
+        let order_id = self.next_order_id();
+
+        let msg = TWSOutgoingMessage::PlaceOrder {
+            order_id,
+            contract: contract.clone(),
+            order: order.clone(),
+            server_version: self.server_version(),
+        };
+        let request = encode(&msg)?;
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        self.orders.write().await.insert(order_id, sender);
+        if let Err(e) = self.send(request).await {
+            self.orders.write().await.remove(&order_id);
+            return Err(e);
+        }
+
+        Ok(OrderSubscription {
+            order_id,
+            receiver,
+            cancels: self.cancels.clone(),
+        })
+    }
+
+    /// Asks TWS to cancel an order, the outcome arrives on the order's receiver.
+    #[instrument(skip(self))]
+    pub async fn cancel_order(&self, order_id: i32) -> Result<(), Error> {
+        let msg = TWSOutgoingMessage::CancelOrder {
+            version: 1,
+            order_id,
+        };
+        self.send(encode(&msg)?).await?;
+        Ok(())
+    }
 
+    #[instrument(skip(self))]
+    pub async fn req_market_data(&self, contract: Contract) -> Result<Subscription, Error> {
         let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
-        self.map.write().await.insert(id, s);
 
         let msg = TWSOutgoingMessage::RequestMarketData {
             req_id: id,
             version: 11,
             contract,
         };
+        let cancel = TWSOutgoingMessage::CancelMarketData {
+            version: 2,
+            req_id: id,
+        };
 
-        let mut buf = Vec::with_capacity(1024);
-        let _ = ser::to_writer(&msg, &mut buf)?;
-
-        self.sink.write().await.send(vec![Bytes::from(buf)]).await?;
-
-        Ok(r)
+        self.send_request(id, &msg, Some(cancel), never_done).await
     }
 
     #[instrument(skip(self))]
-    pub async fn req_contract_data(
-        &self,
-        contract: Contract,
-    ) -> Result<UnboundedReceiver<TWSIncommingMessage>, Box<dyn Error + '_>> {
-        let (s, r) = tokio::sync::mpsc::unbounded_channel();
-
+    pub async fn req_contract_data(&self, contract: Contract) -> Result<Subscription, Error> {
         let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
-        self.map.write().await.insert(id, s);
 
         debug!("Sending req: {}", id);
 
@@ -214,12 +1136,37 @@ impl ClientImpl {
             sec_id: contract.sec_id,
         };
 
-        let mut buf = Vec::with_capacity(1024);
-        let _ = ser::to_writer(&msg, &mut buf)?;
+        self.send_request(id, &msg, None, |msg| {
+            matches!(msg, TWSIncommingMessageImpl::ContractDataEnd { .. })
+        })
+        .await
+    }
+
+    /// Streams the `ExecutionData` of today's executions matching `filter`,
+    /// ending with `ExecutionDataEnd`. Their commission reports carry no
+    /// req_id, join them via `fills` instead.
+    #[instrument(skip(self))]
+    pub async fn req_executions(&self, filter: ExecutionFilter) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
 
-        self.sink.write().await.send(vec![Bytes::from(buf)]).await?;
+        let msg = TWSOutgoingMessage::RequestExecutions {
+            version: 3,
+            req_id: id,
+            client_id: filter.client_id,
+            account: filter.account,
+            time: filter.time,
+            symbol: filter.symbol,
+            sec_type: filter.sec_type,
+            exchange: filter.exchange,
+            side: filter.side,
+        };
 
-        Ok(r)
+        self.send_request(id, &msg, None, |msg| {
+            matches!(msg, TWSIncommingMessageImpl::ExecutionDataEnd { .. })
+        })
+        .await
     }
 
     #[instrument(skip(self))]
@@ -229,11 +1176,8 @@ impl ClientImpl {
         exchange: String,
         underlying_sec_type: String,
         underlying_con_id: i32,
-    ) -> Result<UnboundedReceiver<TWSIncommingMessage>, Box<dyn Error + '_>> {
-        let (s, r) = tokio::sync::mpsc::unbounded_channel();
-
+    ) -> Result<Subscription, Error> {
         let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
-        self.map.write().await.insert(id, s);
 
         println!("Sending req: {}", id);
 
@@ -245,27 +1189,85 @@ impl ClientImpl {
             underlying_con_id,
         };
 
-        let mut buf = Vec::with_capacity(1024);
-        let _ = ser::to_writer(&msg, &mut buf)?;
-
-        self.sink.write().await.send(vec![Bytes::from(buf)]).await?;
-
-        Ok(r)
+        self.send_request(id, &msg, None, |msg| {
+            matches!(
+                msg,
+                TWSIncommingMessageImpl::SecurityDefinitionOptionParameterEnd { .. }
+            )
+        })
+        .await
     }
 
+    /// Returns the earliest time IB has data of the given type for.
+    /// Needs `run` to be driven concurrently.
+    #[instrument(skip(self))]
+    pub async fn req_head_timestamp(
+        &self,
+        contract: &Contract,
+        what_to_show: HistoricalDataType,
+        use_regular_trading_hours: bool,
+    ) -> Result<DateTime<Utc>, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
+
+        let pacing = pacing_key(
+            contract,
+            what_to_show,
+            format!("head|{}", use_regular_trading_hours),
+        );
+
+        let msg = TWSOutgoingMessage::RequestHeadTimestamp {
+            req_id: id,
+            con_id: contract.con_id,
+            symbol: contract.symbol.clone(),
+            sec_type: contract.sec_type.clone(),
+            last_trade_date_or_contract_month: contract.last_trade_date_or_contract_month.clone(),
+            strike: contract.strike,
+            right: contract.right.clone(),
+            multiplier: contract.multiplier.clone(),
+            exchange: contract.exchange.clone(),
+            primary_exchange: contract.primary_exchange.clone(),
+            currency: contract.currency.clone(),
+            local_symbol: contract.local_symbol.clone(),
+            trading_class: contract.trading_class.clone(),
+            include_expired: contract.include_expired,
+            use_regular_trading_hours,
+            what_to_show,
+            format_date: 2,
+        };
+        let cancel = TWSOutgoingMessage::CancelHeadTimestamp { req_id: id };
+
+        let subscription = self
+            .send_paced_request(id, pacing, &msg, Some(cancel), |msg| {
+                matches!(msg, TWSIncommingMessageImpl::HeadTimestamp { .. })
+            })
+            .await?;
+        first_message(subscription, "head timestamp", |msg| match msg {
+            TWSIncommingMessageImpl::HeadTimestamp { head_timestamp, .. } => Some(*head_timestamp),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Returns the number of trades per price over `period`, e.g. "3 days".
+    /// Needs `run` to be driven concurrently.
     #[instrument(skip(self))]
     pub async fn req_histogram_data(
         &self,
         contract: &Contract,
         use_regular_trading_hours: bool,
         period: &str,
-    ) -> Result<UnboundedReceiver<TWSIncommingMessage>, Box<dyn Error + '_>> {
-        let (s, r) = tokio::sync::mpsc::unbounded_channel();
-
+    ) -> Result<Vec<(Currency, i64)>, Error> {
         let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
-        self.map.write().await.insert(id, s);
 
-        println!("Sending req: {}", id);
+        debug!(id, "Sending req");
+
+        let pacing = pacing_key(
+            contract,
+            "histogram",
+            format!("histogram|{}|{}", use_regular_trading_hours, period),
+        );
 
         let msg = TWSOutgoingMessage::RequestHistogramData {
             req_id: id,
@@ -285,13 +1287,581 @@ impl ClientImpl {
             use_regular_trading_hours,
             period: period.to_owned(),
         };
+        let cancel = TWSOutgoingMessage::CancelHistogramData { req_id: id };
 
-        let mut buf = Vec::with_capacity(1024);
-        let _ = ser::to_writer(&msg, &mut buf)?;
+        let subscription = self
+            .send_paced_request(id, pacing, &msg, Some(cancel), |msg| {
+                matches!(msg, TWSIncommingMessageImpl::HistogramData { .. })
+            })
+            .await?;
+        first_message(subscription, "histogram data", |msg| match msg {
+            TWSIncommingMessageImpl::HistogramData { data, .. } => Some(data.clone()),
+            _ => None,
+        })
+        .await
+    }
 
-        self.sink.write().await.send(vec![Bytes::from(buf)]).await?;
+    /// Streams 5 second bars as `RealTimeBars` messages until cancelled.
+    #[instrument(skip(self))]
+    pub async fn req_real_time_bars(
+        &self,
+        contract: &Contract,
+        what_to_show: HistoricalDataType,
+        use_regular_trading_hours: bool,
+    ) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
 
-        Ok(r)
+        let msg = TWSOutgoingMessage::RequestRealTimeBars {
+            version: 3,
+            req_id: id,
+            con_id: contract.con_id,
+            symbol: contract.symbol.clone(),
+            sec_type: contract.sec_type.clone(),
+            last_trade_date_or_contract_month: contract.last_trade_date_or_contract_month.clone(),
+            strike: contract.strike,
+            right: contract.right.clone(),
+            multiplier: contract.multiplier.clone(),
+            exchange: contract.exchange.clone(),
+            primary_exchange: contract.primary_exchange.clone(),
+            currency: contract.currency.clone(),
+            local_symbol: contract.local_symbol.clone(),
+            trading_class: contract.trading_class.clone(),
+            bar_size: 5,
+            what_to_show,
+            use_regular_trading_hours,
+            real_time_bars_options: (),
+        };
+        let cancel = TWSOutgoingMessage::CancelRealTimeBars {
+            version: 1,
+            req_id: id,
+        };
+
+        self.send_request(id, &msg, Some(cancel), never_done).await
+    }
+
+    /// Streams every tick of the given type as `TickByTick` messages until
+    /// cancelled.
+    #[instrument(skip(self))]
+    pub async fn req_tick_by_tick_data(
+        &self,
+        contract: &Contract,
+        tick_type: TickByTickType,
+        ignore_size: bool,
+    ) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
+
+        let msg = TWSOutgoingMessage::ReqTickByTickData {
+            req_id: id,
+            con_id: contract.con_id,
+            symbol: contract.symbol.clone(),
+            sec_type: contract.sec_type.clone(),
+            last_trade_date_or_contract_month: contract.last_trade_date_or_contract_month.clone(),
+            strike: contract.strike,
+            right: contract.right.clone(),
+            multiplier: contract.multiplier.clone(),
+            exchange: contract.exchange.clone(),
+            primary_exchange: contract.primary_exchange.clone(),
+            currency: contract.currency.clone(),
+            local_symbol: contract.local_symbol.clone(),
+            trading_class: contract.trading_class.clone(),
+            tick_type,
+            //live ticks only, `req_historical_ticks` gets past ones
+            number_of_ticks: 0,
+            ignore_size,
+        };
+        let cancel = TWSOutgoingMessage::CancelTickByTickData { req_id: id };
+
+        self.send_request(id, &msg, Some(cancel), never_done).await
+    }
+
+    /// Streams raw `MarketDepth`/`MarketDepthL2` messages for up to
+    /// `num_rows` rows per side, see `req_order_book` for a maintained book.
+    #[instrument(skip(self))]
+    pub async fn req_market_depth(
+        &self,
+        contract: &Contract,
+        num_rows: i32,
+        is_smart_depth: bool,
+    ) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
+
+        let msg = TWSOutgoingMessage::RequestMarketDepth {
+            version: 5,
+            req_id: id,
+            con_id: contract.con_id,
+            symbol: contract.symbol.clone(),
+            sec_type: contract.sec_type.clone(),
+            last_trade_date_or_contract_month: contract.last_trade_date_or_contract_month.clone(),
+            strike: contract.strike,
+            right: contract.right.clone(),
+            multiplier: contract.multiplier.clone(),
+            exchange: contract.exchange.clone(),
+            primary_exchange: contract.primary_exchange.clone(),
+            currency: contract.currency.clone(),
+            local_symbol: contract.local_symbol.clone(),
+            trading_class: contract.trading_class.clone(),
+            num_rows,
+            is_smart_depth,
+            market_depth_options: (),
+        };
+        let cancel = TWSOutgoingMessage::CancelMarketDepth {
+            version: 1,
+            req_id: id,
+            is_smart_depth,
+        };
+
+        self.send_request(id, &msg, Some(cancel), never_done).await
+    }
+
+    /// Like `req_market_depth` but yields the order book after each update.
+    #[instrument(skip(self))]
+    pub async fn req_order_book(
+        &self,
+        contract: &Contract,
+        num_rows: i32,
+        is_smart_depth: bool,
+    ) -> Result<OrderBookSubscription, Error> {
+        let subscription = self
+            .req_market_depth(contract, num_rows, is_smart_depth)
+            .await?;
+        Ok(OrderBookSubscription::new(subscription))
+    }
+
+    /// Lists the exchanges market depth is available from. Needs `run` to be
+    /// driven concurrently.
+    #[instrument(skip(self))]
+    pub async fn req_mkt_depth_exchanges(&self) -> Result<Vec<DepthMktDataDescription>, Error> {
+        let (sender, receiver) = oneshot::channel();
+        self.depth_exchanges.write().await.push(sender);
+        let msg = TWSOutgoingMessage::RequestMktDepthExchanges {};
+        self.send(encode(&msg)?).await?;
+
+        receiver.await.map_err(|_| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionAborted,
+                "client stopped before depth exchanges arrived",
+            ))
+        })
+    }
+
+    /// Returns all open orders, including those placed from TWS or by other
+    /// client ids. Needs `run` to be driven concurrently.
+    #[instrument(skip(self))]
+    pub async fn open_orders(&self) -> Result<Vec<OrderSnapshot>, Error> {
+        let msg = TWSOutgoingMessage::RequestAllOpenOrders { version: 1 };
+        self.collect(
+            &self.open_orders,
+            &self.open_orders_turn,
+            &msg,
+            "open orders",
+        )
+        .await
+    }
+
+    /// Returns the orders that were filled or cancelled, only those placed
+    /// through the API if `api_only`. Needs `run` to be driven concurrently.
+    #[instrument(skip(self))]
+    pub async fn completed_orders(&self, api_only: bool) -> Result<Vec<OrderSnapshot>, Error> {
+        let msg = TWSOutgoingMessage::ReqCompletedOrders { api_only };
+        self.collect(
+            &self.completed_orders,
+            &self.completed_orders_turn,
+            &msg,
+            "completed orders",
+        )
+        .await
+    }
+
+    /// Makes TWS report orders placed from TWS to this client, binding them
+    /// to API order ids if `auto_bind`. Only allowed for client id 0.
+    #[instrument(skip(self))]
+    pub async fn req_auto_open_orders(&self, auto_bind: bool) -> Result<(), Error> {
+        let msg = TWSOutgoingMessage::RequestAutoOpenOrders {
+            version: 1,
+            auto_bind,
+        };
+        self.send(encode(&msg)?).await?;
+        Ok(())
+    }
+
+    /// Downloads up to 1000 ticks starting at `start_date_time` or ending at
+    /// `end_date_time`, leave the other empty. `what_to_show` is one of
+    /// `Trades`, `Midpoint` or `BidAsk` and determines which of the
+    /// `HistoricalTick*` messages arrive. The request finishes with the first
+    /// message flagged `done`.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip(self))]
+    pub async fn req_historical_ticks(
+        &self,
+        contract: &Contract,
+        start_date_time: &str,
+        end_date_time: &str,
+        number_of_ticks: i32,
+        what_to_show: HistoricalDataType,
+        use_regular_trading_hours: bool,
+        ignore_size: bool,
+    ) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
+
+        let pacing = pacing_key(
+            contract,
+            what_to_show,
+            format!(
+                "ticks|{}|{}|{}|{}|{}",
+                start_date_time,
+                end_date_time,
+                number_of_ticks,
+                use_regular_trading_hours,
+                ignore_size
+            ),
+        );
+
+        let msg = TWSOutgoingMessage::ReqHistoricalTicks {
+            req_id: id,
+            con_id: contract.con_id,
+            symbol: contract.symbol.clone(),
+            sec_type: contract.sec_type.clone(),
+            last_trade_date_or_contract_month: contract.last_trade_date_or_contract_month.clone(),
+            strike: contract.strike,
+            right: contract.right.clone(),
+            multiplier: contract.multiplier.clone(),
+            exchange: contract.exchange.clone(),
+            primary_exchange: contract.primary_exchange.clone(),
+            currency: contract.currency.clone(),
+            local_symbol: contract.local_symbol.clone(),
+            trading_class: contract.trading_class.clone(),
+            include_expired: contract.include_expired,
+            start_date_time: start_date_time.into(),
+            end_date_time: end_date_time.into(),
+            number_of_ticks,
+            what_to_show,
+            use_regular_trading_hours,
+            ignore_size,
+            misc_options: (),
+        };
+
+        self.send_paced_request(id, pacing, &msg, None, |msg| match msg {
+            TWSIncommingMessageImpl::HistoricalTick { done, .. }
+            | TWSIncommingMessageImpl::HistoricalTickBidAsk { done, .. }
+            | TWSIncommingMessageImpl::HistoricalTickLast { done, .. } => *done,
+            _ => false,
+        })
+        .await
+    }
+
+    /// Like `req_historical_ticks` but collects the ticks of all its
+    /// messages.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip(self))]
+    pub async fn historical_ticks(
+        &self,
+        contract: &Contract,
+        start_date_time: &str,
+        end_date_time: &str,
+        number_of_ticks: i32,
+        what_to_show: HistoricalDataType,
+        use_regular_trading_hours: bool,
+        ignore_size: bool,
+    ) -> Result<HistoricalTicks, Error> {
+        let mut subscription = self
+            .req_historical_ticks(
+                contract,
+                start_date_time,
+                end_date_time,
+                number_of_ticks,
+                what_to_show,
+                use_regular_trading_hours,
+                ignore_size,
+            )
+            .await?;
+        let mut collected: Option<HistoricalTicks> = None;
+        while let Some(response) = subscription.recv().await {
+            if let Response::Message(msg) = response? {
+                if let Some((ticks, done)) = HistoricalTicks::from_message(msg.get_msg()) {
+                    match collected.as_mut() {
+                        Some(c) => c.append(ticks),
+                        None => collected = Some(ticks),
+                    }
+                    if done {
+                        return Ok(collected.unwrap());
+                    }
+                }
+            }
+        }
+        Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::ConnectionAborted,
+            "client stopped before historical ticks arrived",
+        )))
+    }
+
+    /// Streams `Position` messages for all accounts, a `PositionEnd` marks the
+    /// end of the initial batch. Only one such request can be live, a new
+    /// one ends the previous.
+    #[instrument(skip(self))]
+    pub async fn req_positions(&self) -> Result<Subscription, Error> {
+        let msg = TWSOutgoingMessage::RequestPositions { version: 1 };
+        let cancel = TWSOutgoingMessage::CancelPositions { version: 1 };
+
+        self.send_request(POSITIONS_REQ_ID, &msg, Some(cancel), never_done)
+            .await
+    }
+
+    /// Like `req_positions` but maintains a `PositionBook` from them. With an
+    /// `account_code` it also subscribes to that account's updates to value
+    /// its positions, ending any other `req_account_updates`.
+    #[instrument(skip(self))]
+    pub async fn req_position_book(
+        &self,
+        account_code: Option<&str>,
+    ) -> Result<PositionBookSubscription, Error> {
+        let positions = self.req_positions().await?;
+        Ok(match account_code {
+            Some(account_code) => PositionBookSubscription::with_account_updates(
+                positions,
+                self.req_account_updates(account_code).await?,
+            ),
+            None => PositionBookSubscription::new(positions),
+        })
+    }
+
+    /// Streams `PositionMulti` messages for an account and model code, either
+    /// may be empty for all of them.
+    #[instrument(skip(self))]
+    pub async fn req_positions_multi(
+        &self,
+        account: &str,
+        model_code: &str,
+    ) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        let msg = TWSOutgoingMessage::RequestPositionsMulti {
+            version: 1,
+            req_id: id,
+            account: account.into(),
+            model_code: model_code.into(),
+        };
+        let cancel = TWSOutgoingMessage::CancelPositionsMulti {
+            version: 1,
+            req_id: id,
+        };
+
+        self.send_request(id, &msg, Some(cancel), never_done).await
+    }
+
+    /// Streams `AccountValue`, `PortfolioValue` and `AccountUpdateTime`
+    /// messages for one account. Only one account can be subscribed at a
+    /// time, a new request ends the previous.
+    #[instrument(skip(self))]
+    pub async fn req_account_updates(&self, account_code: &str) -> Result<Subscription, Error> {
+        let msg = TWSOutgoingMessage::RequestAccountData {
+            version: 2,
+            subscribe: true,
+            account_code: account_code.into(),
+        };
+        let cancel = TWSOutgoingMessage::RequestAccountData {
+            version: 2,
+            subscribe: false,
+            account_code: account_code.into(),
+        };
+
+        self.send_request(ACCOUNT_UPDATES_REQ_ID, &msg, Some(cancel), never_done)
+            .await
+    }
+
+    /// Like `req_account_updates` but maintains the account's `AccountState`
+    /// from them.
+    #[instrument(skip(self))]
+    pub async fn req_account_states(
+        &self,
+        account_code: &str,
+    ) -> Result<AccountStatesSubscription, Error> {
+        let subscription = self.req_account_updates(account_code).await?;
+        Ok(AccountStatesSubscription::new(subscription))
+    }
+
+    /// Streams `AccountSummary` messages for the accounts in `group`, usually
+    /// "All". TWS sends the requested tags once followed by
+    /// `AccountSummaryEnd`, and updates them every 3 minutes after.
+    #[instrument(skip(self))]
+    pub async fn req_account_summary(
+        &self,
+        group: &str,
+        tags: &[AccountSummaryTag],
+    ) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
+
+        let tags = tags
+            .iter()
+            .map(|t| t.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let msg = TWSOutgoingMessage::RequestAccountSummary {
+            version: 1,
+            req_id: id,
+            group: group.into(),
+            tags,
+        };
+        let cancel = TWSOutgoingMessage::CancelAccountSummary {
+            version: 1,
+            req_id: id,
+        };
+
+        self.send_request(id, &msg, Some(cancel), never_done).await
+    }
+
+    /// Like `req_account_summary` but maintains an `AccountState` per account
+    /// from them.
+    #[instrument(skip(self))]
+    pub async fn req_account_summary_states(
+        &self,
+        group: &str,
+        tags: &[AccountSummaryTag],
+    ) -> Result<AccountStatesSubscription, Error> {
+        let subscription = self.req_account_summary(group, tags).await?;
+        Ok(AccountStatesSubscription::new(subscription))
+    }
+
+    /// Streams `AccountUpdateMulti` messages for an account and model code.
+    /// Unlike `req_account_updates` several of these can be active at once.
+    #[instrument(skip(self))]
+    pub async fn req_account_updates_multi(
+        &self,
+        account: &str,
+        model_code: &str,
+        ledger_and_nlv: bool,
+    ) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
+
+        let msg = TWSOutgoingMessage::RequestAccountUpdatesMulti {
+            version: 1,
+            req_id: id,
+            account: account.into(),
+            model_code: model_code.into(),
+            ledger_and_nlv,
+        };
+        let cancel = TWSOutgoingMessage::CancelAccountUpdatesMulti {
+            version: 1,
+            req_id: id,
+        };
+
+        self.send_request(id, &msg, Some(cancel), never_done).await
+    }
+
+    /// Streams `PnL` messages with the daily, unrealized and realized PnL of
+    /// an account, optionally limited to a model code.
+    #[instrument(skip(self))]
+    pub async fn req_pnl(&self, account: &str, model_code: &str) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
+
+        let msg = TWSOutgoingMessage::ReqPnL {
+            req_id: id,
+            account: account.into(),
+            model_code: model_code.into(),
+        };
+        let cancel = TWSOutgoingMessage::CancelPnL { req_id: id };
+
+        self.send_request(id, &msg, Some(cancel), never_done).await
+    }
+
+    /// Streams `PnLSingle` messages for a single position of an account.
+    #[instrument(skip(self))]
+    pub async fn req_pnl_single(
+        &self,
+        account: &str,
+        model_code: &str,
+        con_id: i32,
+    ) -> Result<Subscription, Error> {
+        let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
+
+        debug!(id, "Sending req");
+
+        let msg = TWSOutgoingMessage::ReqPnLSingle {
+            req_id: id,
+            account: account.into(),
+            model_code: model_code.into(),
+            con_id,
+        };
+        let cancel = TWSOutgoingMessage::CancelPnLSingle { req_id: id };
+
+        self.send_request(id, &msg, Some(cancel), never_done).await
+    }
+
+    /// Downloads the bars between `start` and `end`, split into as many
+    /// requests as IB's duration limits for `bar_size` require. The requests
+    /// are paced like any other, the bars arrive in order and without the
+    /// duplicates of overlapping requests. Ranges without data, e.g. weekends,
+    /// are skipped.
+    pub fn historical_range<'a, BarSize: ValidBarSize + Debug + Copy + 'a>(
+        &'a self,
+        contract: &'a Contract,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bar_size: BarSize,
+        what_to_show: HistoricalDataType,
+    ) -> impl Stream<Item = Result<HistoricalBarData, Error>> + 'a {
+        let chunks = historical::chunks(start, end, BarSize::NAME);
+        futures::stream::unfold(
+            (chunks.into_iter(), None),
+            move |(mut chunks, mut last)| async move {
+                let (chunk_end, duration) = chunks.next()?;
+                let bars = match self
+                    .historical_chunk(contract, chunk_end, duration, bar_size, what_to_show)
+                    .await
+                {
+                    Ok(bars) => bars,
+                    Err(e) => return Some((vec![Err(e)], (vec![].into_iter(), last))),
+                };
+                let bars = historical::take_new_bars(bars, start, end, &mut last);
+                Some((bars.into_iter().map(Ok).collect::<Vec<_>>(), (chunks, last)))
+            },
+        )
+        .flat_map(futures::stream::iter)
+    }
+
+    async fn historical_chunk<BarSize: ValidBarSize + Debug>(
+        &self,
+        contract: &Contract,
+        end: DateTime<Utc>,
+        duration: &str,
+        bar_size: BarSize,
+        what_to_show: HistoricalDataType,
+    ) -> Result<Vec<HistoricalBarData>, Error> {
+        let subscription = self
+            .req_historical_data(
+                contract,
+                &historical::format_end_date_time(end),
+                duration,
+                bar_size,
+                what_to_show,
+                false,
+                false,
+            )
+            .await?;
+        let bars = first_message(subscription, "historical data", |msg| match msg {
+            TWSIncommingMessageImpl::HistoricalData { bars, .. } => Some(bars.clone()),
+            _ => None,
+        })
+        .await;
+        match bars {
+            Err(Error::Tws {
+                code: 162, message, ..
+            }) if message.contains("returned no data") => Ok(vec![]),
+            bars => bars,
+        }
     }
 
     //reqHistoricalData(int tickerId, Contract contract, string endDateTime, string durationString, string barSizeSetting, string whatToShow, int useRTH, int formatDate, bool keepUpToDate, List<TagValue> chartOptions)
@@ -305,14 +1875,24 @@ impl ClientImpl {
         what_to_show: HistoricalDataType,
         use_regular_trading_hours: bool,
         keep_up_to_date: bool,
-    ) -> Result<UnboundedReceiver<TWSIncommingMessage>, Box<dyn Error + '_>> {
-        let (s, r) = tokio::sync::mpsc::unbounded_channel();
-
+    ) -> Result<Subscription, Error> {
         let id = self.req_id.fetch_add(1, atomic::Ordering::Relaxed);
-        self.map.write().await.insert(id, s);
 
         debug!(id, "Sending req");
 
+        let pacing = pacing_key(
+            contract,
+            what_to_show,
+            format!(
+                "bars|{}|{}|{}|{}|{}",
+                end_date_time,
+                duration,
+                BarSize::NAME,
+                use_regular_trading_hours,
+                keep_up_to_date
+            ),
+        );
+
         let msg = TWSOutgoingMessage::RequestHistoricalData {
             req_id: id,
             con_id: contract.con_id,
@@ -337,12 +1917,43 @@ impl ClientImpl {
             keep_up_to_date,
             chart_options: (),
         };
+        let cancel = TWSOutgoingMessage::CancelHistoricalData {
+            version: 1,
+            req_id: id,
+        };
+        //with keep_up_to_date the initial bars are followed by updates until cancelled
+        let is_done: fn(&TWSIncommingMessageImpl) -> bool = if keep_up_to_date {
+            never_done
+        } else {
+            |msg| matches!(msg, TWSIncommingMessageImpl::HistoricalData { .. })
+        };
 
-        let mut buf = Vec::with_capacity(1024);
-        let _ = ser::to_writer(&msg, &mut buf)?;
-
-        self.sink.write().await.send(vec![Bytes::from(buf)]).await?;
+        self.send_paced_request(id, pacing, &msg, Some(cancel), is_done)
+            .await
+    }
 
-        Ok(r)
+    /// Requests `duration` of bars up to now, kept up to date, and merges the
+    /// updates into a series of updated and closed bars.
+    #[instrument(skip(self))]
+    pub async fn req_live_bars<BarSize: ValidBarSize + Debug>(
+        &self,
+        contract: &Contract,
+        duration: &str,
+        bar_size: BarSize,
+        what_to_show: HistoricalDataType,
+        use_regular_trading_hours: bool,
+    ) -> Result<LiveBarSeriesSubscription, Error> {
+        let subscription = self
+            .req_historical_data(
+                contract,
+                "",
+                duration,
+                bar_size,
+                what_to_show,
+                use_regular_trading_hours,
+                true,
+            )
+            .await?;
+        Ok(LiveBarSeriesSubscription::new(subscription))
     }
 }
@@ -1,150 +1,272 @@
-use std::{
-    cmp::{max, min},
-    fmt::Write,
-    io::ErrorKind,
-};
-
 use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::{
+    error::Error,
+    recording::{Direction, Recorder},
+};
 use tokio_util::codec;
-use tracing::{debug, error, info, instrument, trace, warn};
+use tracing::trace;
 
 const MAX: usize = 8 * 1024 * 1024;
 
-#[derive(Debug)]
-pub enum TWSFrame {
-    Incomming {
-        data: Bytes,
-        splits: Vec<std::ops::Range<usize>>,
-    },
-    Outgoing(Bytes),
+/// A decoded frame: its payload and where each field in it ends. Fields
+/// are sliced out of the payload when read instead of up front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TWSFrame {
+    data: Bytes,
+    //the position of each field's terminating \0, or the payload's length
+    //for an unterminated last field
+    ends: Vec<u32>,
 }
 
-pub type DecodedMessage = Vec<Bytes>;
+impl TWSFrame {
+    pub fn new(data: Bytes) -> TWSFrame {
+        let mut ends = Vec::with_capacity(data.len() / 4);
+        calc_splits_dst(&data, &mut ends);
+        if data.last().is_some_and(|c| *c != 0) {
+            *ends.last_mut().unwrap() = data.len() as u32;
+        }
+        TWSFrame { data, ends }
+    }
 
-#[derive(Debug)]
-pub struct TWSCodec {}
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
 
-impl TWSCodec {
-    pub fn new() -> TWSCodec {
-        TWSCodec {}
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
     }
-}
 
-#[cfg(target_arch = "x86")]
-use std::arch::x86::*;
-#[cfg(target_arch = "x86_64")]
-use std::arch::x86_64::*;
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        self.range(i).map(|r| &self.data[r])
+    }
 
-#[inline(always)]
-fn round_up<const N: usize>(n: usize) -> usize {
-    (n + (N - 1)) / N * N
+    /// Like `get` but shares the payload's buffer.
+    pub fn get_bytes(&self, i: usize) -> Option<Bytes> {
+        self.range(i).map(|r| self.data.slice(r))
+    }
+
+    pub fn first(&self) -> Option<&[u8]> {
+        self.get(0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        (0..self.len()).filter_map(move |i| self.get(i))
+    }
+
+    /// The frame as sent, without its length prefix.
+    pub fn payload(&self) -> &Bytes {
+        &self.data
+    }
+
+    pub fn to_fields(&self) -> DecodedMessage {
+        (0..self.len()).filter_map(|i| self.get_bytes(i)).collect()
+    }
+
+    fn range(&self, i: usize) -> Option<std::ops::Range<usize>> {
+        let end = *self.ends.get(i)? as usize;
+        let start = match i {
+            0 => 0,
+            _ => self.ends[i - 1] as usize + 1,
+        };
+        Some(start..end)
+    }
 }
 
-#[inline(always)]
-unsafe fn nonz_index(data: __m128i) -> __m128i {
-    let indx_const = 0xFEDCBA9876543210u64;
-    let pshufbcnst = _mm_set_epi8(
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0E, 0x0C, 0x0A, 0x08, 0x06, 0x04, 0x02,
-        0x00,
-    );
-    let mut null_mask = _mm_cmpeq_epi8(data, _mm_setzero_si128());
-    null_mask = _mm_srli_epi64(null_mask, 4);
-    null_mask = _mm_shuffle_epi8(null_mask, pshufbcnst);
-    let mask64 = !(_mm_cvtsi128_si64x(null_mask) as u64);
-
-    let indx64 = _pext_u64(indx_const, mask64) as i64;
-    let indx = _mm_cvtsi64x_si128(indx64);
-    let indx_024 = indx;
-    let indx_135 = _mm_srli_epi64(indx, 4);
-    let indx_res = _mm_unpacklo_epi8(indx_024, indx_135);
-
-    _mm_and_si128(indx_res, _mm_set1_epi8(0x0F))
+impl From<DecodedMessage> for TWSFrame {
+    fn from(fields: DecodedMessage) -> TWSFrame {
+        let mut data = BytesMut::with_capacity(fields.iter().map(|f| f.len() + 1).sum());
+        for field in fields {
+            data.put(field);
+            data.put_u8(0);
+        }
+        TWSFrame::new(data.freeze())
+    }
 }
 
-#[inline(always)]
-unsafe fn extract<const N: i32>(input: __m128i, out_ptr: *const u32, i: usize) {
-    let high = _mm_bsrli_si128(input, N);
-    let shuf_high = _mm_cvtepu8_epi32(high);
-    let added = _mm_add_epi32(shuf_high, _mm_set1_epi32(i as i32));
-    _mm_storeu_si128(out_ptr.add(N as usize) as *mut __m128i, added);
+pub type DecodedMessage = Vec<Bytes>;
+
+#[derive(Debug)]
+pub struct TWSCodec {
+    recorder: Option<Recorder>,
+    //the first frame TWS sends is its answer to the version handshake
+    handshaken: bool,
 }
 
-#[inline(always)]
-unsafe fn calc_splits_dst(src: &[u8], dst: &mut Vec<u32>) {
-    let len = src.len();
-    debug_assert!(len < (u32::MAX as usize));
+impl TWSCodec {
+    pub fn new() -> TWSCodec {
+        TWSCodec {
+            recorder: None,
+            handshaken: false,
+        }
+    }
 
-    let estimated_req = len / 4;
-    if dst.capacity() < estimated_req {
-        dst.reserve(estimated_req - dst.capacity());
+    /// Like `new` but also writes every frame to `recorder`.
+    pub fn with_recorder(recorder: Recorder) -> TWSCodec {
+        TWSCodec {
+            recorder: Some(recorder),
+            handshaken: false,
+        }
     }
 
+    fn record(&self, direction: Direction, payload: &[u8]) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(direction, payload);
+        }
+    }
+}
+
+/// Appends the positions of all \0 bytes in `src` to `dst`, plus the last
+/// position if `src` doesn't end in one. Uses AVX2 or SSE4.1 and BMI2 when
+/// the CPU has them, `memchr` otherwise.
+pub fn calc_splits_dst(src: &[u8], dst: &mut Vec<u32>) {
+    debug_assert!(src.len() < (u32::MAX as usize));
+
+    #[cfg(target_arch = "x86_64")]
     {
-        let in_ptr = src.as_ptr();
+        if is_x86_feature_detected!("avx2") {
+            unsafe { x86::calc_splits_dst_avx2(src, dst) };
+        } else if is_x86_feature_detected!("sse4.1")
+            && is_x86_feature_detected!("ssse3")
+            && is_x86_feature_detected!("bmi2")
+        {
+            unsafe { x86::calc_splits_dst_sse(src, dst) };
+        } else {
+            calc_splits_dst_memchr(src, dst);
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    calc_splits_dst_memchr(src, dst);
 
-        let iter_len = round_up::<32>(len);
-        for i in (0..iter_len).step_by(32) {
-            let vec = _mm_loadu_si128(in_ptr.add(i) as *const __m128i);
-            let vec2 = _mm_loadu_si128(in_ptr.add(i + 16) as *const __m128i);
+    push_last(src, dst);
+}
 
-            let zero_eq = _mm_cmpeq_epi8(vec, _mm_setzero_si128());
-            let zero_eq2 = _mm_cmpeq_epi8(vec2, _mm_setzero_si128());
-            let zero_idx = nonz_index(zero_eq);
-            let zero_idx2 = nonz_index(zero_eq2);
+/// The portable `calc_splits_dst`, without the last position.
+pub fn calc_splits_dst_memchr(src: &[u8], dst: &mut Vec<u32>) {
+    calc_splits_dst_memchr_from(src, 0, dst);
+}
 
-            let num_ones = (_mm_movemask_epi8(zero_eq) as u32).count_ones() as usize;
-            let num_ones2 = (_mm_movemask_epi8(zero_eq2) as u32).count_ones() as usize;
+//finishes what the SIMD paths leave over
+fn calc_splits_dst_memchr_from(src: &[u8], from: usize, dst: &mut Vec<u32>) {
+    dst.extend(memchr::memchr_iter(0, &src[from..]).map(|i| (from + i) as u32));
+}
 
-            if num_ones > 0 {
-                let num_nulls = min(num_ones, len - i);
-                let rounded = round_up::<4>(num_nulls);
+fn push_last(src: &[u8], dst: &mut Vec<u32>) {
+    if let Some(c) = src.last() {
+        if *c != 0 {
+            dst.push((src.len() - 1) as u32);
+        }
+    }
+}
 
-                let out_ptr = dst.as_mut_ptr().add(dst.len());
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
 
-                extract::<0>(zero_idx, out_ptr, i);
-                if rounded >= 4 {
-                    extract::<4>(zero_idx, out_ptr, i);
-                }
-                if rounded >= 8 {
-                    extract::<8>(zero_idx, out_ptr, i);
-                }
-                if rounded >= 12 {
-                    extract::<12>(zero_idx, out_ptr, i);
-                }
+    #[inline(always)]
+    fn round_up<const N: usize>(n: usize) -> usize {
+        n.div_ceil(N) * N
+    }
 
-                dst.set_len(dst.len() + num_nulls);
-            }
+    #[inline]
+    #[target_feature(enable = "sse4.1,ssse3,bmi2")]
+    unsafe fn nonz_index(data: __m128i) -> __m128i {
+        let indx_const = 0xFEDCBA9876543210u64;
+        let pshufbcnst = _mm_set_epi8(
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0E, 0x0C, 0x0A, 0x08, 0x06, 0x04,
+            0x02, 0x00,
+        );
+        let mut null_mask = _mm_cmpeq_epi8(data, _mm_setzero_si128());
+        null_mask = _mm_srli_epi64(null_mask, 4);
+        null_mask = _mm_shuffle_epi8(null_mask, pshufbcnst);
+        let mask64 = !(_mm_cvtsi128_si64x(null_mask) as u64);
+
+        let indx64 = _pext_u64(indx_const, mask64) as i64;
+        let indx = _mm_cvtsi64x_si128(indx64);
+        let indx_024 = indx;
+        let indx_135 = _mm_srli_epi64(indx, 4);
+        let indx_res = _mm_unpacklo_epi8(indx_024, indx_135);
+
+        _mm_and_si128(indx_res, _mm_set1_epi8(0x0F))
+    }
 
-            if num_ones2 > 0 {
-                let num_nulls = min(num_ones2, len - (i + 16));
-                let rounded = round_up::<4>(num_nulls);
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn extract<const N: i32>(input: __m128i, out_ptr: *mut u32, i: usize) {
+        let high = _mm_bsrli_si128(input, N);
+        let shuf_high = _mm_cvtepu8_epi32(high);
+        let added = _mm_add_epi32(shuf_high, _mm_set1_epi32(i as i32));
+        _mm_storeu_si128(out_ptr.add(N as usize) as *mut __m128i, added);
+    }
 
-                let out_ptr = dst.as_mut_ptr().add(dst.len());
+    //stores the positions of the \0s among 16 bytes, 4 at a time, so up to
+    //16 slots past the end of `dst` may be written
+    #[inline]
+    #[target_feature(enable = "sse4.1,ssse3,bmi2")]
+    unsafe fn extract_block(vec: __m128i, dst: &mut Vec<u32>, i: usize) {
+        let zero_eq = _mm_cmpeq_epi8(vec, _mm_setzero_si128());
+        let num_nulls = (_mm_movemask_epi8(zero_eq) as u32).count_ones() as usize;
+        if num_nulls == 0 {
+            return;
+        }
+        let zero_idx = nonz_index(zero_eq);
+        let rounded = round_up::<4>(num_nulls);
+        let out_ptr = dst.as_mut_ptr().add(dst.len());
 
-                extract::<0>(zero_idx2, out_ptr, i + 16);
-                if rounded >= 4 {
-                    extract::<4>(zero_idx2, out_ptr, i + 16);
-                }
-                if rounded >= 8 {
-                    extract::<8>(zero_idx2, out_ptr, i + 16);
-                }
-                if rounded >= 12 {
-                    extract::<12>(zero_idx2, out_ptr, i + 16);
-                }
+        extract::<0>(zero_idx, out_ptr, i);
+        if rounded >= 8 {
+            extract::<4>(zero_idx, out_ptr, i);
+        }
+        if rounded >= 12 {
+            extract::<8>(zero_idx, out_ptr, i);
+        }
+        if rounded >= 16 {
+            extract::<12>(zero_idx, out_ptr, i);
+        }
 
-                dst.set_len(dst.len() + num_nulls);
-            }
+        dst.set_len(dst.len() + num_nulls);
+    }
+
+    /// Needs SSE4.1, SSSE3 and BMI2.
+    #[target_feature(enable = "sse4.1,ssse3,bmi2")]
+    pub unsafe fn calc_splits_dst_sse(src: &[u8], dst: &mut Vec<u32>) {
+        let len = src.len();
+        let in_ptr = src.as_ptr();
+        let full = len / 32 * 32;
+
+        for i in (0..full).step_by(32) {
+            dst.reserve(32);
+            let vec = _mm_loadu_si128(in_ptr.add(i) as *const __m128i);
+            let vec2 = _mm_loadu_si128(in_ptr.add(i + 16) as *const __m128i);
+            extract_block(vec, dst, i);
+            extract_block(vec2, dst, i + 16);
         }
+        super::calc_splits_dst_memchr_from(src, full, dst);
     }
-    if let Some(c) = src.last() {
-        if *c != 0 {
-            dst.push((len - 1) as u32);
+
+    /// Needs AVX2.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn calc_splits_dst_avx2(src: &[u8], dst: &mut Vec<u32>) {
+        let len = src.len();
+        let in_ptr = src.as_ptr();
+        let full = len / 32 * 32;
+
+        for i in (0..full).step_by(32) {
+            let vec = _mm256_loadu_si256(in_ptr.add(i) as *const __m256i);
+            let zero_eq = _mm256_cmpeq_epi8(vec, _mm256_setzero_si256());
+            let mut mask = _mm256_movemask_epi8(zero_eq) as u32;
+            while mask != 0 {
+                dst.push((i + mask.trailing_zeros() as usize) as u32);
+                mask &= mask - 1;
+            }
         }
+        super::calc_splits_dst_memchr_from(src, full, dst);
     }
 }
-fn calc_splits(src: &[u8]) -> Vec<std::ops::Range<usize>> {
+/// The ranges between consecutive positions found by `calc_splits_dst`.
+pub fn calc_splits(src: &[u8]) -> Vec<std::ops::Range<usize>> {
     let mut dst = Vec::new();
-    unsafe { calc_splits_dst(src, &mut dst) };
+    calc_splits_dst(src, &mut dst);
 
     dst.windows(2)
         .map(|o| std::ops::Range {
@@ -155,8 +277,8 @@ fn calc_splits(src: &[u8]) -> Vec<std::ops::Range<usize>> {
 }
 
 impl codec::Decoder for TWSCodec {
-    type Item = DecodedMessage;
-    type Error = std::io::Error;
+    type Item = TWSFrame;
+    type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         if src.len() < 4 {
@@ -172,10 +294,7 @@ impl codec::Decoder for TWSCodec {
         // Check that the length is not too large to avoid a denial of
         // service attack where the server runs out of memory.
         if length > MAX {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Frame of length {} is too large.", length),
-            ));
+            return Err(Error::FrameTooLarge { length, max: MAX });
         }
 
         if src.len() < 4 + length {
@@ -192,43 +311,20 @@ impl codec::Decoder for TWSCodec {
 
         //println!("Decoding: {:?}", src);
         src.advance(4); //drop packet size bytes
-        let mut data = src.split_to(length).freeze();
+        let data = src.split_to(length).freeze();
         trace!("Recieved data: {:?}", data);
-        /* Ok(Some(TWSFrame::Incomming {
-            data,
-            splits: vec![],
-        })) */
-        //Ok(Some(data))
-        let splits = data
-            .iter()
-            .enumerate()
-            .filter_map(|(i, c)| match *c {
-                0 => Some(i),
-                _ => None,
-            })
-            .collect::<Vec<_>>();
-
-        let mut v = vec![];
-        let mut i = 0usize;
-        for split in splits {
-            let at = min(max(split - i + 1, 1), data.len() - 1);
-
-            let mut res = data.split_to(at);
-
-            i += res.len();
-            match res.last() {
-                Some(c) if *c == 0 => res.truncate(res.len() - 1),
-                _ => {}
-            };
-            v.push(res);
+        if self.handshaken {
+            self.record(Direction::Inbound, &data);
+        } else {
+            self.handshaken = true;
+            self.record(Direction::Handshake, &data);
         }
-
-        Ok(Some(v))
+        Ok(Some(TWSFrame::new(data)))
     }
 }
 
 impl codec::Encoder<DecodedMessage> for TWSCodec {
-    type Error = std::io::Error;
+    type Error = Error;
 
     fn encode(&mut self, item: DecodedMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
         let sizes = item
@@ -238,19 +334,14 @@ impl codec::Encoder<DecodedMessage> for TWSCodec {
 
         let size = sizes.iter().sum::<u32>() - 1u32;
         dst.extend_from_slice(&size.to_be_bytes());
+        let start = dst.len();
         for s in item.iter().take(item.len() - 1) {
             dst.put(&s[..]);
             dst.put(&b"\0"[..]);
         }
         dst.put(&item.last().unwrap()[..]);
         trace!("Writing: {:?}", dst);
-
-        /* let data = match &item {
-            TWSFrame::Incomming { data, splits } => data,
-            TWSFrame::Outgoing(data) => data,
-        };
-        println!("Writing: {:?}", data);
-        dst.extend_from_slice(&data[..]);*/
+        self.record(Direction::Outbound, &dst[start..]);
         Ok(())
     }
 }
@@ -258,10 +349,10 @@ impl codec::Encoder<DecodedMessage> for TWSCodec {
 #[cfg(test)]
 mod tests {
 
-    use bytes::{BufMut, BytesMut};
+    use bytes::{BufMut, Bytes, BytesMut};
     use tokio_util::codec::Decoder;
 
-    use super::TWSCodec;
+    use super::{Error, TWSCodec, TWSFrame};
 
     const STR: &str = include!("sample.txt");
     const DATA: &[u8] = STR.as_bytes();
@@ -297,6 +388,42 @@ mod tests {
         assert_eq!(res, splits);
     }
 
+    #[test]
+    fn splitters_agree() {
+        let dense = vec![0u8; 100];
+        let mut sparse = vec![b'a'; 77];
+        sparse[3] = 0;
+        sparse[40] = 0;
+        for input in [DATA, &dense[..], &sparse[..], &b"no nulls"[..], &b""[..]] {
+            let mut expected = vec![];
+            super::calc_splits_dst_memchr(input, &mut expected);
+            super::push_last(input, &mut expected);
+
+            let mut res = vec![];
+            super::calc_splits_dst(input, &mut res);
+            assert_eq!(expected, res);
+
+            #[cfg(target_arch = "x86_64")]
+            {
+                if is_x86_feature_detected!("avx2") {
+                    let mut res = vec![];
+                    unsafe { super::x86::calc_splits_dst_avx2(input, &mut res) };
+                    super::push_last(input, &mut res);
+                    assert_eq!(expected, res);
+                }
+                if is_x86_feature_detected!("sse4.1")
+                    && is_x86_feature_detected!("ssse3")
+                    && is_x86_feature_detected!("bmi2")
+                {
+                    let mut res = vec![];
+                    unsafe { super::x86::calc_splits_dst_sse(input, &mut res) };
+                    super::push_last(input, &mut res);
+                    assert_eq!(expected, res);
+                }
+            }
+        }
+    }
+
     #[test]
     fn memchr_test() {
         let simple = DATA
@@ -384,6 +511,39 @@ mod tests {
         );
     } */
 
+    #[test]
+    fn too_large_frame_is_rejected() {
+        let mut c = TWSCodec::new();
+        let mut buf = BytesMut::new();
+        buf.put_u32(super::MAX as u32 + 1);
+        assert!(matches!(
+            c.decode(&mut buf),
+            Err(Error::FrameTooLarge { length, max }) if length == max + 1
+        ));
+    }
+
+    #[test]
+    fn decoded_frame_slices_fields() {
+        let mut c = TWSCodec::new();
+        let mut buf = BytesMut::new();
+        const DATA: &[u8] = b"151\0\020210309 22:54:30 CET\0";
+        buf.put_u32(DATA.len() as u32);
+        buf.put(DATA);
+        let frame = c.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(3, frame.len());
+        assert_eq!(
+            vec![&b"151"[..], b"", b"20210309 22:54:30 CET"],
+            frame.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(None, frame.get(3));
+
+        //a last field without its \0
+        let frame = TWSFrame::new(Bytes::from_static(b"9\01\042"));
+        assert_eq!(Some(&b"42"[..]), frame.get(2));
+        let fields = frame.to_fields();
+        assert_eq!(fields, TWSFrame::from(fields.clone()).to_fields());
+    }
+
     #[test]
     fn empty_bytes_behaves_as_expected() {
         let mut buf = BytesMut::from(&b""[..]);
@@ -0,0 +1,140 @@
+use std::fmt::{self, Display};
+use std::io;
+
+use super::serde;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong talking to TWS.
+#[derive(Debug)]
+pub enum Error {
+    /// Connecting, reading from or writing to the socket failed.
+    Io(io::Error),
+    /// The connection closed or misbehaved before the session was set up.
+    Handshake(String),
+    /// TWS answered with a server version outside the range we speak.
+    VersionMismatch { min: i32, max: i32, actual: String },
+    /// A `RateLimit` that allows no messages at all.
+    InvalidRateLimit { per_second: f64 },
+    /// A frame's length prefix exceeds what the codec accepts.
+    FrameTooLarge { length: usize, max: usize },
+    /// A message couldn't be encoded or decoded.
+    Serde(serde::error::Error),
+    /// TWS reported an error, `req_id` is -1 for errors not tied to a request.
+    Tws {
+        req_id: i32,
+        code: i32,
+        message: String,
+    },
+}
+
+/// How serious an error code reported by TWS is.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    /// Status notices, e.g. 2104 "market data farm connection is OK".
+    Info,
+    /// The request carries on, possibly degraded, e.g. 10167 delayed data.
+    Warning,
+    /// The request failed and TWS won't send anything more for it.
+    RequestFatal,
+    /// The session itself is affected, e.g. 1100 connectivity lost.
+    Connection,
+}
+
+impl Severity {
+    pub fn of(code: i32) -> Severity {
+        match code {
+            502 | 504 | 1100 | 1101 | 1102 | 1300 | 2110 => Severity::Connection,
+            2104 | 2106 | 2107 | 2108 | 2119 | 2158 => Severity::Info,
+            202 | 399 | 10090 | 10167 | 2100..=2169 => Severity::Warning,
+            _ => Severity::RequestFatal,
+        }
+    }
+}
+
+impl Error {
+    /// The severity of an error reported by TWS, `None` for our own errors.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            Error::Tws { code, .. } => Some(Severity::of(*code)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Handshake(msg) => write!(f, "handshake failed: {}", msg),
+            Error::VersionMismatch { min, max, actual } => write!(
+                f,
+                "server version mismatch: expected {} to {}, got {}",
+                min, max, actual
+            ),
+            Error::InvalidRateLimit { per_second } => write!(
+                f,
+                "invalid rate limit of {} messages per second",
+                per_second
+            ),
+            Error::FrameTooLarge { length, max } => {
+                write!(f, "frame of length {} is too large, max is {}", length, max)
+            }
+            Error::Serde(e) => write!(f, "serde error: {}", e),
+            Error::Tws {
+                req_id,
+                code,
+                message,
+            } => write!(f, "TWS error {} for req {}: {}", code, req_id, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Serde(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde::error::Error> for Error {
+    fn from(e: serde::error::Error) -> Self {
+        Error::Serde(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Severity};
+
+    #[test]
+    fn classifies_tws_error_codes() {
+        assert_eq!(Severity::Info, Severity::of(2104));
+        assert_eq!(Severity::Warning, Severity::of(2103));
+        assert_eq!(Severity::Warning, Severity::of(10167));
+        assert_eq!(Severity::Connection, Severity::of(1100));
+        assert_eq!(Severity::Connection, Severity::of(1102));
+        assert_eq!(Severity::RequestFatal, Severity::of(200));
+        assert_eq!(Severity::RequestFatal, Severity::of(162));
+    }
+
+    #[test]
+    fn only_tws_errors_have_a_severity() {
+        let tws = Error::Tws {
+            req_id: 1,
+            code: 200,
+            message: "No security definition has been found for the request".to_owned(),
+        };
+        assert_eq!(Some(Severity::RequestFatal), tws.severity());
+        assert_eq!(None, Error::Handshake("closed".to_owned()).severity());
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{
+    messages::{Execution, TWSIncommingMessageImpl},
+    positions::PositionContract,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommissionReport {
+    pub exec_id: String,
+    pub commission: f64,
+    pub currency: String,
+    pub realized_pnl: Option<f64>,
+    pub yield_: Option<f64>,
+    pub yield_redemption_date: Option<i32>,
+}
+
+/// An execution together with its commission report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    //0 for orders placed outside the API
+    pub order_id: i32,
+    pub contract: PositionContract,
+    pub execution: Execution,
+    pub commission: CommissionReport,
+}
+
+//TWS may never send the other half, e.g. for executions reported before a
+//reconnect, so an unmatched one is dropped once this many newer ones of its
+//kind arrived
+const MAX_PENDING: usize = 1024;
+
+//waiting halves by exec_id, oldest first
+#[derive(Debug, Clone)]
+struct Pending<T> {
+    entries: HashMap<String, (u64, T)>,
+    //may still hold ids that were matched or inserted again since
+    order: VecDeque<(u64, String)>,
+    next_seq: u64,
+}
+
+impl<T> Default for Pending<T> {
+    fn default() -> Self {
+        Pending {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            next_seq: 0,
+        }
+    }
+}
+
+impl<T> Pending<T> {
+    fn insert(&mut self, exec_id: String, value: T) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.order.push_back((seq, exec_id.clone()));
+        self.entries.insert(exec_id, (seq, value));
+        while self.order.len() > MAX_PENDING {
+            let (seq, exec_id) = self.order.pop_front().unwrap();
+            if self.entries.get(&exec_id).is_some_and(|(s, _)| *s == seq) {
+                self.entries.remove(&exec_id);
+            }
+        }
+    }
+
+    fn remove(&mut self, exec_id: &str) -> Option<T> {
+        self.entries.remove(exec_id).map(|(_, value)| value)
+    }
+}
+
+/// Joins `ExecutionData` with the `CommissionsReport` for the same `exec_id`.
+/// TWS sends the report after the execution, but doesn't promise so, hence
+/// whichever arrives first waits for the other, up to `MAX_PENDING` newer
+/// ones.
+#[derive(Debug, Clone, Default)]
+pub struct FillJoiner {
+    executions: Pending<(i32, PositionContract, Execution)>,
+    commissions: Pending<CommissionReport>,
+}
+
+impl FillJoiner {
+    pub fn new() -> FillJoiner {
+        FillJoiner::default()
+    }
+
+    /// Applies an execution or commission message, returns the fill it
+    /// completed. A replayed execution completes its fill again.
+    pub fn apply_message(&mut self, msg: &TWSIncommingMessageImpl) -> Option<Fill> {
+        match *msg {
+            TWSIncommingMessageImpl::ExecutionData {
+                order_id,
+                ref contract,
+                ref execution,
+                ..
+            } => match self.commissions.remove(&execution.exec_id) {
+                Some(commission) => Some(Fill {
+                    order_id,
+                    contract: contract.into(),
+                    execution: execution.clone(),
+                    commission,
+                }),
+                None => {
+                    self.executions.insert(
+                        execution.exec_id.clone(),
+                        (order_id, contract.into(), execution.clone()),
+                    );
+                    None
+                }
+            },
+            TWSIncommingMessageImpl::CommissionsReport {
+                exec_id,
+                commission,
+                currency,
+                realized_pnl,
+                yield_,
+                yield_redemption_date,
+                ..
+            } => {
+                let commission = CommissionReport {
+                    exec_id: exec_id.to_owned(),
+                    commission,
+                    currency: currency.to_owned(),
+                    realized_pnl,
+                    yield_,
+                    yield_redemption_date,
+                };
+                match self.executions.remove(exec_id) {
+                    Some((order_id, contract, execution)) => Some(Fill {
+                        order_id,
+                        contract,
+                        execution,
+                        commission,
+                    }),
+                    None => {
+                        self.commissions.insert(exec_id.to_owned(), commission);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FillJoiner, MAX_PENDING};
+    use crate::tws::messages::{Execution, OrderContract, TWSIncommingMessageImpl};
+    use fixed_macro::types::I44F20 as dec;
+
+    fn execution_data(exec_id: &str) -> TWSIncommingMessageImpl<'static> {
+        TWSIncommingMessageImpl::ExecutionData {
+            req_id: -1,
+            order_id: 7,
+            contract: OrderContract {
+                con_id: 265598,
+                symbol: "AAPL",
+                sec_type: "STK",
+                last_trade_date_or_contract_month: "",
+                strike: dec!(0),
+                right: "",
+                multiplier: "",
+                exchange: "ISLAND",
+                currency: "USD",
+                local_symbol: "AAPL",
+                trading_class: "NMS",
+            },
+            execution: Execution {
+                exec_id: exec_id.to_owned(),
+                time: "20210422  10:30:00".to_owned(),
+                account: "DU123".to_owned(),
+                exchange: "ISLAND".to_owned(),
+                side: "BOT".to_owned(),
+                shares: 100.0,
+                price: 134.5,
+                perm_id: 1234,
+                client_id: 0,
+                liquidation: 0,
+                cum_qty: 100.0,
+                avg_price: 134.5,
+                order_ref: "".to_owned(),
+                ev_rule: "".to_owned(),
+                ev_multiplier: None,
+                model_code: "".to_owned(),
+                last_liquidity: 2,
+            },
+        }
+    }
+
+    fn commission(exec_id: &'static str) -> TWSIncommingMessageImpl<'static> {
+        TWSIncommingMessageImpl::CommissionsReport {
+            msg_version: 1,
+            exec_id,
+            commission: 1.0,
+            currency: "USD",
+            realized_pnl: None,
+            yield_: None,
+            yield_redemption_date: None,
+        }
+    }
+
+    #[test]
+    fn joins_by_exec_id_in_either_order() {
+        let mut joiner = FillJoiner::new();
+        assert!(joiner.apply_message(&execution_data("a")).is_none());
+        assert!(joiner.apply_message(&commission("b")).is_none());
+
+        let fill = joiner.apply_message(&commission("a")).unwrap();
+        assert_eq!(7, fill.order_id);
+        assert_eq!("a", fill.execution.exec_id);
+        assert_eq!(1.0, fill.commission.commission);
+
+        let fill = joiner.apply_message(&execution_data("b")).unwrap();
+        assert_eq!("b", fill.commission.exec_id);
+        assert!(joiner.apply_message(&commission("a")).is_none());
+    }
+
+    #[test]
+    fn drops_the_oldest_unmatched_halves() {
+        let mut joiner = FillJoiner::new();
+        for i in 0..=MAX_PENDING {
+            joiner.apply_message(&execution_data(&i.to_string()));
+        }
+        assert_eq!(MAX_PENDING, joiner.executions.entries.len());
+        assert!(joiner.apply_message(&commission("0")).is_none());
+        assert!(joiner.apply_message(&commission("1")).is_some());
+
+        //the matched execution is gone, the commission for "0" waits
+        assert_eq!(MAX_PENDING - 1, joiner.executions.entries.len());
+        assert_eq!(1, joiner.commissions.entries.len());
+        assert!(joiner.executions.order.len() <= MAX_PENDING);
+    }
+}
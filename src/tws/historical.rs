@@ -0,0 +1,110 @@
+use chrono::{DateTime, Duration, Utc};
+
+use super::messages::HistoricalBarData;
+
+/// The longest span a single `req_historical_data` may cover for a bar size
+/// and the duration string requesting it, following IB's step size table.
+pub fn max_chunk(bar_size: &str) -> (Duration, &'static str) {
+    match bar_size {
+        "1 sec" => (Duration::minutes(30), "1800 S"),
+        "5 secs" => (Duration::hours(1), "3600 S"),
+        "10 secs" | "15 secs" => (Duration::hours(4), "14400 S"),
+        "30 secs" => (Duration::hours(8), "28800 S"),
+        "1 min" => (Duration::days(1), "1 D"),
+        "2 mins" | "3 mins" => (Duration::days(2), "2 D"),
+        "5 mins" | "10 mins" => (Duration::weeks(1), "1 W"),
+        "15 mins" | "20 mins" | "30 mins" => (Duration::weeks(2), "2 W"),
+        "1 hour" | "2 hours" | "3 hours" | "4 hours" | "8 hours" => (Duration::days(28), "1 M"),
+        _ => (Duration::days(365), "1 Y"),
+    }
+}
+
+/// Splits `start..end` into the end times and durations of the requests
+/// covering it, oldest first. A chunk's duration may reach further back
+/// than the previous chunk's end, the overlap is dropped by `take_new_bars`.
+pub fn chunks(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    bar_size: &str,
+) -> Vec<(DateTime<Utc>, &'static str)> {
+    let (span, duration) = max_chunk(bar_size);
+    let mut chunks = vec![];
+    let mut chunk_start = start;
+    while chunk_start < end {
+        let chunk_end = (chunk_start + span).min(end);
+        chunks.push((chunk_end, duration));
+        chunk_start = chunk_end;
+    }
+    chunks
+}
+
+/// Formats an end time the way `RequestHistoricalData` expects it.
+pub fn format_end_date_time(end: DateTime<Utc>) -> String {
+    end.format("%Y%m%d %H:%M:%S GMT").to_string()
+}
+
+/// Keeps the bars within `start..end` that are newer than `last`, in order,
+/// and moves `last` to the newest of them.
+pub fn take_new_bars(
+    mut bars: Vec<HistoricalBarData>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    last: &mut Option<DateTime<Utc>>,
+) -> Vec<HistoricalBarData> {
+    bars.sort_by_key(|b| b.date);
+    bars.retain(|b| b.date >= start && b.date < end && !matches!(last, Some(l) if b.date <= *l));
+    if let Some(b) = bars.last() {
+        *last = Some(b.date);
+    }
+    bars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunks, take_new_bars};
+    use crate::tws::messages::HistoricalBarData;
+    use chrono::{Duration, TimeZone, Utc};
+    use fixed_macro::types::I44F20 as dec;
+
+    #[test]
+    fn splits_ranges_by_bar_size() {
+        let start = Utc.timestamp_opt(1618812000, 0).unwrap();
+        let end = start + Duration::hours(20);
+
+        let c = chunks(start, end, "30 secs");
+        assert_eq!(
+            vec![
+                (start + Duration::hours(8), "28800 S"),
+                (start + Duration::hours(16), "28800 S"),
+                (end, "28800 S"),
+            ],
+            c
+        );
+        assert_eq!(vec![(end, "1 D")], chunks(start, end, "1 min"));
+        assert!(chunks(end, start, "1 min").is_empty());
+    }
+
+    #[test]
+    fn drops_overlapping_and_out_of_range_bars() {
+        let start = Utc.timestamp_opt(1618812000, 0).unwrap();
+        let bar = |minutes: i64| HistoricalBarData {
+            date: start + Duration::minutes(minutes),
+            open: dec!(1),
+            high: dec!(1),
+            low: dec!(1),
+            close: dec!(1),
+            volume: None,
+            wap: None,
+            trade_count: None,
+        };
+        let end = start + Duration::minutes(10);
+
+        let mut last = None;
+        let first = take_new_bars(vec![bar(1), bar(-1), bar(0)], start, end, &mut last);
+        assert_eq!(vec![bar(0), bar(1)], first);
+
+        let second = take_new_bars(vec![bar(0), bar(1), bar(2), bar(10)], start, end, &mut last);
+        assert_eq!(vec![bar(2)], second);
+        assert_eq!(Some(bar(2).date), last);
+    }
+}
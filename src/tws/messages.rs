@@ -1,12 +1,18 @@
 use crate::tws::serde::custom_chrono;
 use std::collections::HashSet;
 
-use super::{codec::DecodedMessage, serde::de::Deserializer, serde::error::Result};
+use super::{
+    codec::TWSFrame,
+    error::Error,
+    serde::de::Deserializer,
+    serde::error::{self as de_error, Result},
+    versions,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use twsapi::core::contract;
 
-type Currency = fixed::FixedI64<fixed::types::extra::U20>;
+pub type Currency = fixed::FixedI64<fixed::types::extra::U20>;
 
 bitflags! {
     #[derive(Deserialize)]
@@ -17,6 +23,211 @@ bitflags! {
     }
 }
 
+bitflags! {
+    #[derive(Deserialize)]
+    pub struct TickAttributeLast : i32 {
+        const PAST_LIMIT = 0b01;
+        const UNREPORTED = 0b10;
+    }
+}
+
+bitflags! {
+    #[derive(Deserialize)]
+    pub struct TickAttributeBidAsk : i32 {
+        const BID_PAST_LOW = 0b01;
+        const ASK_PAST_HIGH = 0b10;
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthOperation {
+    #[serde(rename = "0")]
+    Insert,
+    #[serde(rename = "1")]
+    Update,
+    #[serde(rename = "2")]
+    Delete,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthSide {
+    #[serde(rename = "0")]
+    Ask,
+    #[serde(rename = "1")]
+    Bid,
+}
+
+/// An exchange market depth can be requested from, see
+/// `req_mkt_depth_exchanges`.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct DepthMktDataDescription {
+    pub exchange: String,
+    pub sec_type: String,
+    pub listing_exchange: String,
+    pub service_data_type: String,
+    pub agg_group: Option<i32>,
+}
+
+/// The values `req_account_summary` can ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccountSummaryTag {
+    AccountType,
+    NetLiquidation,
+    TotalCashValue,
+    SettledCash,
+    AccruedCash,
+    BuyingPower,
+    EquityWithLoanValue,
+    PreviousEquityWithLoanValue,
+    GrossPositionValue,
+    RegTEquity,
+    RegTMargin,
+    Sma,
+    InitMarginReq,
+    MaintMarginReq,
+    AvailableFunds,
+    ExcessLiquidity,
+    Cushion,
+    FullInitMarginReq,
+    FullMaintMarginReq,
+    FullAvailableFunds,
+    FullExcessLiquidity,
+    LookAheadNextChange,
+    LookAheadInitMarginReq,
+    LookAheadMaintMarginReq,
+    LookAheadAvailableFunds,
+    LookAheadExcessLiquidity,
+    HighestSeverity,
+    DayTradesRemaining,
+    Leverage,
+}
+
+impl AccountSummaryTag {
+    pub const ALL: [AccountSummaryTag; 29] = [
+        AccountSummaryTag::AccountType,
+        AccountSummaryTag::NetLiquidation,
+        AccountSummaryTag::TotalCashValue,
+        AccountSummaryTag::SettledCash,
+        AccountSummaryTag::AccruedCash,
+        AccountSummaryTag::BuyingPower,
+        AccountSummaryTag::EquityWithLoanValue,
+        AccountSummaryTag::PreviousEquityWithLoanValue,
+        AccountSummaryTag::GrossPositionValue,
+        AccountSummaryTag::RegTEquity,
+        AccountSummaryTag::RegTMargin,
+        AccountSummaryTag::Sma,
+        AccountSummaryTag::InitMarginReq,
+        AccountSummaryTag::MaintMarginReq,
+        AccountSummaryTag::AvailableFunds,
+        AccountSummaryTag::ExcessLiquidity,
+        AccountSummaryTag::Cushion,
+        AccountSummaryTag::FullInitMarginReq,
+        AccountSummaryTag::FullMaintMarginReq,
+        AccountSummaryTag::FullAvailableFunds,
+        AccountSummaryTag::FullExcessLiquidity,
+        AccountSummaryTag::LookAheadNextChange,
+        AccountSummaryTag::LookAheadInitMarginReq,
+        AccountSummaryTag::LookAheadMaintMarginReq,
+        AccountSummaryTag::LookAheadAvailableFunds,
+        AccountSummaryTag::LookAheadExcessLiquidity,
+        AccountSummaryTag::HighestSeverity,
+        AccountSummaryTag::DayTradesRemaining,
+        AccountSummaryTag::Leverage,
+    ];
+
+    /// The tag's name on the wire, which is also the key `AccountValue` and
+    /// `AccountUpdateMulti` report it under.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountSummaryTag::AccountType => "AccountType",
+            AccountSummaryTag::NetLiquidation => "NetLiquidation",
+            AccountSummaryTag::TotalCashValue => "TotalCashValue",
+            AccountSummaryTag::SettledCash => "SettledCash",
+            AccountSummaryTag::AccruedCash => "AccruedCash",
+            AccountSummaryTag::BuyingPower => "BuyingPower",
+            AccountSummaryTag::EquityWithLoanValue => "EquityWithLoanValue",
+            AccountSummaryTag::PreviousEquityWithLoanValue => "PreviousEquityWithLoanValue",
+            AccountSummaryTag::GrossPositionValue => "GrossPositionValue",
+            AccountSummaryTag::RegTEquity => "RegTEquity",
+            AccountSummaryTag::RegTMargin => "RegTMargin",
+            AccountSummaryTag::Sma => "SMA",
+            AccountSummaryTag::InitMarginReq => "InitMarginReq",
+            AccountSummaryTag::MaintMarginReq => "MaintMarginReq",
+            AccountSummaryTag::AvailableFunds => "AvailableFunds",
+            AccountSummaryTag::ExcessLiquidity => "ExcessLiquidity",
+            AccountSummaryTag::Cushion => "Cushion",
+            AccountSummaryTag::FullInitMarginReq => "FullInitMarginReq",
+            AccountSummaryTag::FullMaintMarginReq => "FullMaintMarginReq",
+            AccountSummaryTag::FullAvailableFunds => "FullAvailableFunds",
+            AccountSummaryTag::FullExcessLiquidity => "FullExcessLiquidity",
+            AccountSummaryTag::LookAheadNextChange => "LookAheadNextChange",
+            AccountSummaryTag::LookAheadInitMarginReq => "LookAheadInitMarginReq",
+            AccountSummaryTag::LookAheadMaintMarginReq => "LookAheadMaintMarginReq",
+            AccountSummaryTag::LookAheadAvailableFunds => "LookAheadAvailableFunds",
+            AccountSummaryTag::LookAheadExcessLiquidity => "LookAheadExcessLiquidity",
+            AccountSummaryTag::HighestSeverity => "HighestSeverity",
+            AccountSummaryTag::DayTradesRemaining => "DayTradesRemaining",
+            AccountSummaryTag::Leverage => "Leverage",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<AccountSummaryTag> {
+        AccountSummaryTag::ALL
+            .iter()
+            .copied()
+            .find(|t| t.as_str() == key)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TickByTickType {
+    Last,
+    AllLast,
+    BidAsk,
+    MidPoint,
+}
+
+/// A single tick from `req_tick_by_tick_data`, tagged with its type.
+#[derive(Deserialize, PartialEq, Debug)]
+pub enum TickByTick<'a> {
+    #[serde(rename = "1")]
+    Last {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        time: DateTime<Utc>,
+        price: Currency,
+        size: i64,
+        attrib: TickAttributeLast,
+        exchange: &'a str,
+        special_conditions: &'a str,
+    },
+    #[serde(rename = "2")]
+    AllLast {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        time: DateTime<Utc>,
+        price: Currency,
+        size: i64,
+        attrib: TickAttributeLast,
+        exchange: &'a str,
+        special_conditions: &'a str,
+    },
+    #[serde(rename = "3")]
+    BidAsk {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        time: DateTime<Utc>,
+        bid_price: Currency,
+        ask_price: Currency,
+        bid_size: i64,
+        ask_size: i64,
+        attrib: TickAttributeBidAsk,
+    },
+    #[serde(rename = "4")]
+    MidPoint {
+        #[serde(with = "chrono::serde::ts_seconds")]
+        time: DateTime<Utc>,
+        mid_point: Currency,
+    },
+}
+
 #[repr(i32)]
 #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MarketDataType {
@@ -236,43 +447,375 @@ pub struct Contract<'a> {
     combo_legs_description: &'a str,
 }
 
+/// The contract fields sent along with open orders and positions.
 #[derive(Deserialize, PartialEq, Debug)]
 pub struct OrderContract<'a> {
-    con_id: i32,
-    symbol: &'a str,
-    sec_type: &'a str,
-    last_trade_date_or_contract_month: &'a str,
-    strike: Currency,
-    right: &'a str,
-    multiplier: &'a str,
-    exchange: &'a str,
-    currency: &'a str,
-    local_symbol: &'a str,
+    pub con_id: i32,
+    pub symbol: &'a str,
+    pub sec_type: &'a str,
+    pub last_trade_date_or_contract_month: &'a str,
+    pub strike: Currency,
+    pub right: &'a str,
+    pub multiplier: &'a str,
+    pub exchange: &'a str,
+    pub currency: &'a str,
+    pub local_symbol: &'a str,
     //primary_exchange: &'a str,
-    trading_class: &'a str,
+    pub trading_class: &'a str,
     //include_expired: bool,
     //sec_id_type: &'a str,
     //sec_id: &'a str,
     //combo_legs_description: &'a str,
 }
 
+/// Same as `OrderContract` but with the primary exchange in place of the
+/// exchange, as sent with `PortfolioValue`.
 #[derive(Deserialize, PartialEq, Debug)]
+pub struct PortfolioContract<'a> {
+    pub con_id: i32,
+    pub symbol: &'a str,
+    pub sec_type: &'a str,
+    pub last_trade_date_or_contract_month: &'a str,
+    pub strike: Currency,
+    pub right: &'a str,
+    pub multiplier: &'a str,
+    pub primary_exchange: &'a str,
+    pub currency: &'a str,
+    pub local_symbol: &'a str,
+    pub trading_class: &'a str,
+}
+
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
 #[serde(rename_all = "PascalCase")]
 pub enum InnerOrderStatus {
+    ApiPending,
+    PendingSubmit,
+    PendingCancel,
+    PreSubmitted,
     Submitted,
+    ApiCancelled,
     Cancelled,
+    Filled,
+    Inactive,
 }
-#[derive(Deserialize, PartialEq, Debug)]
+
+impl InnerOrderStatus {
+    /// Whether TWS will send no further updates for an order in this state.
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self,
+            InnerOrderStatus::ApiCancelled
+                | InnerOrderStatus::Cancelled
+                | InnerOrderStatus::Filled
+                | InnerOrderStatus::Inactive
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Action {
+    Buy,
+    Sell,
+    #[serde(rename = "SSHORT")]
+    ShortSell,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TimeInForce {
+    Day,
+    Gtc,
+    Ioc,
+    Gtd,
+    Opg,
+    Fok,
+    Dtc,
+}
+
+/// The subset of IB's order fields that can be set through `place_order`.
+/// Everything else is sent with TWS' defaults.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Order {
+    pub action: Action,
+    pub total_quantity: f64,
+    /// "MKT", "LMT", "STP", "STP LMT", "TRAIL", "REL", ...
+    pub order_type: String,
+    pub lmt_price: Option<Currency>,
+    pub aux_price: Option<Currency>,
+    pub tif: TimeInForce,
+    pub oca_group: String,
+    pub oca_type: i32,
+    pub account: String,
+    pub open_close: String,
+    pub order_ref: String,
+    pub transmit: bool,
+    pub parent_id: i32,
+    pub outside_rth: bool,
+    pub hidden: bool,
+    pub all_or_none: bool,
+    pub not_held: bool,
+    pub what_if: bool,
+    pub good_after_time: String,
+    pub good_till_date: String,
+    pub model_code: String,
+    pub trail_stop_price: Option<Currency>,
+    pub trailing_percent: Option<f64>,
+    pub algo_strategy: String,
+    pub algo_params: Vec<(String, String)>,
+    //only set on orders reported by TWS
+    pub client_id: i32,
+    pub perm_id: i32,
+}
+
+impl Default for Order {
+    fn default() -> Self {
+        Order {
+            action: Action::Buy,
+            total_quantity: 0.0,
+            order_type: String::new(),
+            lmt_price: None,
+            aux_price: None,
+            tif: TimeInForce::Day,
+            oca_group: String::new(),
+            oca_type: 0,
+            account: String::new(),
+            open_close: String::new(),
+            order_ref: String::new(),
+            transmit: true,
+            parent_id: 0,
+            outside_rth: false,
+            hidden: false,
+            all_or_none: false,
+            not_held: false,
+            what_if: false,
+            good_after_time: String::new(),
+            good_till_date: String::new(),
+            model_code: String::new(),
+            trail_stop_price: None,
+            trailing_percent: None,
+            algo_strategy: String::new(),
+            algo_params: Vec::new(),
+            client_id: 0,
+            perm_id: 0,
+        }
+    }
+}
+
+impl Order {
+    pub fn market(action: Action, total_quantity: f64) -> Order {
+        Order {
+            action,
+            total_quantity,
+            order_type: "MKT".into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn limit(action: Action, total_quantity: f64, lmt_price: Currency) -> Order {
+        Order {
+            action,
+            total_quantity,
+            order_type: "LMT".into(),
+            lmt_price: Some(lmt_price),
+            ..Default::default()
+        }
+    }
+
+    pub fn stop(action: Action, total_quantity: f64, stop_price: Currency) -> Order {
+        Order {
+            action,
+            total_quantity,
+            order_type: "STP".into(),
+            aux_price: Some(stop_price),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct OrderState<'a> {
+    pub status: InnerOrderStatus,
+    pub init_margin_before: Option<f64>,
+    pub maint_margin_before: Option<f64>,
+    pub equity_with_loan_before: Option<f64>,
+    pub init_margin_change: Option<f64>,
+    pub maint_margin_change: Option<f64>,
+    pub equity_with_loan_change: Option<f64>,
+    pub init_margin_after: Option<f64>,
+    pub maint_margin_after: Option<f64>,
+    pub equity_with_loan_after: Option<f64>,
+    pub commission: Option<f64>,
+    pub min_commission: Option<f64>,
+    pub max_commission: Option<f64>,
+    pub commission_currency: &'a str,
+    pub warning_text: &'a str,
+}
+
+/// The state of an order that is no longer working.
+#[derive(PartialEq, Debug)]
+pub struct CompletedOrderState<'a> {
+    pub status: InnerOrderStatus,
+    pub filled_quantity: Option<f64>,
+    pub completed_time: &'a str,
+    //why it completed, e.g. "Cancelled by Trader"
+    pub completed_status: &'a str,
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct HistoricalBarData {
     #[serde(with = "custom_chrono")]
-    date: DateTime<Utc>,
-    open: Currency,
-    high: Currency,
-    low: Currency,
-    close: Currency,
-    volume: Option<i64>,
-    wap: Option<Currency>,
-    trade_count: Option<i32>, //only valid for TRADES req
+    pub date: DateTime<Utc>,
+    pub open: Currency,
+    pub high: Currency,
+    pub low: Currency,
+    pub close: Currency,
+    pub volume: Option<i64>,
+    pub wap: Option<Currency>,
+    pub trade_count: Option<i32>, //only valid for TRADES req
+}
+
+/// A 5 second bar from `req_real_time_bars`, `time` is the bar's start.
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+pub struct RealTimeBar {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub time: DateTime<Utc>,
+    pub open: Currency,
+    pub high: Currency,
+    pub low: Currency,
+    pub close: Currency,
+    pub volume: i64,
+    pub wap: Currency,
+    pub count: i32,
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+pub struct HistoricalTickData {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub time: DateTime<Utc>,
+    //always 0
+    unused: i32,
+    pub price: Currency,
+    pub size: i64,
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+pub struct HistoricalTickBidAskData {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub time: DateTime<Utc>,
+    #[serde(deserialize_with = "historical_bid_ask_attribute")]
+    pub attrib: TickAttributeBidAsk,
+    pub bid_price: Currency,
+    pub ask_price: Currency,
+    pub bid_size: i64,
+    pub ask_size: i64,
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct HistoricalTickLastData {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub time: DateTime<Utc>,
+    pub attrib: TickAttributeLast,
+    pub price: Currency,
+    pub size: i64,
+    pub exchange: String,
+    pub special_conditions: String,
+}
+
+/// The ticks of a historical ticks request, of the type `what_to_show`
+/// asked for.
+#[derive(PartialEq, Debug, Clone)]
+pub enum HistoricalTicks {
+    Midpoint(Vec<HistoricalTickData>),
+    BidAsk(Vec<HistoricalTickBidAskData>),
+    Last(Vec<HistoricalTickLastData>),
+}
+
+impl HistoricalTicks {
+    /// The ticks of a `HistoricalTick*` message and whether it's the last.
+    pub fn from_message(msg: &TWSIncommingMessageImpl) -> Option<(HistoricalTicks, bool)> {
+        match msg {
+            TWSIncommingMessageImpl::HistoricalTick { ticks, done, .. } => {
+                Some((HistoricalTicks::Midpoint(ticks.clone()), *done))
+            }
+            TWSIncommingMessageImpl::HistoricalTickBidAsk { ticks, done, .. } => {
+                Some((HistoricalTicks::BidAsk(ticks.clone()), *done))
+            }
+            TWSIncommingMessageImpl::HistoricalTickLast { ticks, done, .. } => {
+                Some((HistoricalTicks::Last(ticks.clone()), *done))
+            }
+            _ => None,
+        }
+    }
+
+    /// Appends ticks of the same type, others are ignored.
+    pub fn append(&mut self, other: HistoricalTicks) {
+        match (self, other) {
+            (HistoricalTicks::Midpoint(a), HistoricalTicks::Midpoint(mut b)) => a.append(&mut b),
+            (HistoricalTicks::BidAsk(a), HistoricalTicks::BidAsk(mut b)) => a.append(&mut b),
+            (HistoricalTicks::Last(a), HistoricalTicks::Last(mut b)) => a.append(&mut b),
+            _ => {}
+        }
+    }
+}
+
+/// Narrows down the executions returned by `req_executions`, empty fields
+/// match everything.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ExecutionFilter {
+    pub client_id: i32,
+    pub account: String,
+    //"yyyymmdd hh:mm:ss", only executions after it are returned
+    pub time: String,
+    pub symbol: String,
+    pub sec_type: String,
+    pub exchange: String,
+    //"BUY" or "SELL"
+    pub side: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Execution {
+    pub exec_id: String,
+    pub time: String,
+    pub account: String,
+    pub exchange: String,
+    //"BOT" or "SLD"
+    pub side: String,
+    pub shares: f64,
+    pub price: f64,
+    pub perm_id: i32,
+    pub client_id: i32,
+    pub liquidation: i32,
+    pub cum_qty: f64,
+    pub avg_price: f64,
+    pub order_ref: String,
+    pub ev_rule: String,
+    pub ev_multiplier: Option<f64>,
+    pub model_code: String,
+    pub last_liquidity: i32,
+}
+
+//historical ticks put ask_past_high in bit 0, tick-by-tick puts bid_past_low there
+fn historical_bid_ask_attribute<'de, D>(
+    deserializer: D,
+) -> std::result::Result<TickAttributeBidAsk, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mask = i32::deserialize(deserializer)?;
+    let mut attrib = TickAttributeBidAsk::empty();
+    attrib.set(TickAttributeBidAsk::ASK_PAST_HIGH, mask & 0b01 != 0);
+    attrib.set(TickAttributeBidAsk::BID_PAST_LOW, mask & 0b10 != 0);
+    Ok(attrib)
+}
+
+//TWS reports unset doubles as Double.MAX_VALUE
+fn unset_double<'de, D>(deserializer: D) -> std::result::Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<f64>::deserialize(deserializer)?.filter(|v| *v != f64::MAX))
 }
 
 mod historical_bar_data_update {
@@ -320,7 +863,280 @@ mod historical_bar_data_update {
     }
 }
 
-#[allow(dead_code)]
+mod open_order {
+    use super::{
+        CompletedOrderState, Currency, InnerOrderStatus, Order, OrderContract, OrderState,
+    };
+    use crate::tws::{serde::de::Since, versions};
+    use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+    use std::fmt;
+
+    //TWS reports unset doubles as Double.MAX_VALUE
+    fn unset(v: Option<f64>) -> Option<f64> {
+        v.filter(|v| *v != f64::MAX)
+    }
+
+    fn price(v: Option<f64>) -> Option<Currency> {
+        v.and_then(Currency::checked_from_num)
+    }
+
+    struct Fields<A>(A);
+
+    impl<'de, A: SeqAccess<'de>> Fields<A> {
+        fn next<T: Deserialize<'de>>(&mut self) -> Result<T, A::Error> {
+            self.0
+                .next_element()?
+                .ok_or_else(|| de::Error::custom("open order ended early"))
+        }
+
+        fn skip(&mut self, n: usize) -> Result<(), A::Error> {
+            for _ in 0..n {
+                self.next::<&'de str>()?;
+            }
+            Ok(())
+        }
+
+        //delta_neutral_order_type .. reference_price_type, the delta neutral
+        //order spans `delta_neutral_fields` fields when set
+        fn skip_vol_order_params(&mut self, delta_neutral_fields: usize) -> Result<(), A::Error> {
+            let delta_neutral_order_type: &str = self.next()?;
+            self.skip(1)?; //delta_neutral_aux_price
+            if !delta_neutral_order_type.is_empty() {
+                self.skip(delta_neutral_fields)?;
+            }
+            self.skip(2) //continuous_update, reference_price_type
+        }
+
+        //combo legs, smart combo routing params and scale order params
+        fn skip_combo_and_scale_params(&mut self) -> Result<(), A::Error> {
+            let combo_legs: usize = self.next::<i32>()? as usize;
+            self.skip(combo_legs * 8)?;
+            let order_combo_legs: usize = self.next::<i32>()? as usize;
+            self.skip(order_combo_legs)?;
+            let smart_combo_routing_params: usize = self.next::<i32>()? as usize;
+            self.skip(smart_combo_routing_params * 2)?;
+            self.skip(2)?; //scale_init_level_size, scale_subs_level_size
+            let scale_price_increment: Option<f64> = unset(self.next()?);
+            if scale_price_increment.is_some_and(|p| p > 0.0) {
+                self.skip(7)?;
+            }
+            let hedge_type: &str = self.next()?;
+            if !hedge_type.is_empty() {
+                self.skip(1)?;
+            }
+            Ok(())
+        }
+
+        //not_held .. algo_params
+        fn read_not_held_and_algo(&mut self, order: &mut Order) -> Result<(), A::Error> {
+            order.not_held = self.next()?;
+            if self.next::<bool>()? {
+                self.skip(3)?; //delta neutral contract
+            }
+            order.algo_strategy = self.next()?;
+            if !order.algo_strategy.is_empty() {
+                let n = self.next::<i32>()?;
+                for _ in 0..n {
+                    order.algo_params.push((self.next()?, self.next()?));
+                }
+            }
+            Ok(())
+        }
+
+        //randomize flags, peg to bench params and conditions
+        fn skip_randomize_and_conditions(&mut self, order: &Order) -> Result<(), A::Error> {
+            self.skip(2)?; //randomize_size, randomize_price
+            if order.order_type == "PEG BENCH" {
+                self.skip(5)?;
+            }
+            let conditions = self.next::<i32>()?;
+            for _ in 0..conditions {
+                let fields = match self.next::<i32>()? {
+                    1 => 6,     //price
+                    3 | 4 => 3, //time, margin
+                    5 => 4,     //execution
+                    6 | 7 => 5, //volume, percent change
+                    t => return Err(de::Error::custom(format!("unknown condition type {}", t))),
+                };
+                self.skip(fields)?;
+            }
+            if conditions > 0 {
+                self.skip(2)?; //conditions_ignore_rth, conditions_cancel_order
+            }
+            Ok(())
+        }
+    }
+
+    type OpenOrder<'a> = (i32, OrderContract<'a>, Order, OrderState<'a>);
+
+    struct OpenOrderVisitor;
+
+    impl<'de> Visitor<'de> for OpenOrderVisitor {
+        type Value = OpenOrder<'de>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an open order message")
+        }
+
+        // Field order follows EDecoder's open order decoding for server
+        // versions 150 and 151. Fields Order doesn't model are skipped.
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            let mut f = Fields(seq);
+            let mut order = Order::default();
+
+            let order_id = f.next()?;
+            let contract = f.next()?;
+            order.action = f.next()?;
+            order.total_quantity = f.next()?;
+            order.order_type = f.next()?;
+            order.lmt_price = price(f.next()?);
+            order.aux_price = price(f.next()?);
+            order.tif = f.next()?;
+            order.oca_group = f.next()?;
+            order.account = f.next()?;
+            order.open_close = f.next()?;
+            f.skip(1)?; //origin
+            order.order_ref = f.next()?;
+            order.client_id = f.next()?;
+            order.perm_id = f.next()?;
+            order.outside_rth = f.next()?;
+            order.hidden = f.next()?;
+            f.skip(1)?; //discretionary_amt
+            order.good_after_time = f.next()?;
+            f.skip(5)?; //shares allocation, fa group, method, percentage and profile
+            order.model_code = f.next()?;
+            order.good_till_date = f.next()?;
+            f.skip(12)?; //rule80a .. stock_range_upper
+            f.skip(3)?; //display_size, block_order, sweep_to_fill
+            order.all_or_none = f.next()?;
+            f.skip(1)?; //min_qty
+            order.oca_type = f.next()?;
+            f.skip(3)?; //e_trade_only, firm_quote_only, nbbo_price_cap
+            order.parent_id = f.next()?;
+            f.skip(3)?; //trigger_method, volatility, volatility_type
+            f.skip_vol_order_params(8)?;
+            order.trail_stop_price = price(f.next()?);
+            order.trailing_percent = unset(f.next()?);
+            f.skip(3)?; //basis_points, basis_points_type, combo_legs_description
+            f.skip_combo_and_scale_params()?;
+            f.skip(3)?; //opt_out_smart_routing, clearing_account, clearing_intent
+            f.read_not_held_and_algo(&mut order)?;
+            f.skip(1)?; //solicited
+            order.what_if = f.next()?;
+
+            let order_state = OrderState {
+                status: f.next::<InnerOrderStatus>()?,
+                init_margin_before: unset(f.next()?),
+                maint_margin_before: unset(f.next()?),
+                equity_with_loan_before: unset(f.next()?),
+                init_margin_change: unset(f.next()?),
+                maint_margin_change: unset(f.next()?),
+                equity_with_loan_change: unset(f.next()?),
+                init_margin_after: unset(f.next()?),
+                maint_margin_after: unset(f.next()?),
+                equity_with_loan_after: unset(f.next()?),
+                commission: unset(f.next()?),
+                min_commission: unset(f.next()?),
+                max_commission: unset(f.next()?),
+                commission_currency: f.next()?,
+                warning_text: f.next()?,
+            };
+
+            f.skip_randomize_and_conditions(&order)?;
+            f.skip(8)?; //adjusted order params
+            f.skip(3)?; //soft dollar tier
+            f.skip(4)?; //cash_qty .. discretionary_up_to_limit_price
+            f.next::<Since<{ versions::PRICE_MGMT_ALGO }, &str>>()?; //use_price_mgmt_algo
+
+            Ok((order_id, contract, order, order_state))
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<OpenOrder<'de>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(usize::MAX, OpenOrderVisitor)
+    }
+
+    type CompletedOrder<'a> = (OrderContract<'a>, Order, CompletedOrderState<'a>);
+
+    struct CompletedOrderVisitor;
+
+    impl<'de> Visitor<'de> for CompletedOrderVisitor {
+        type Value = CompletedOrder<'de>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a completed order message")
+        }
+
+        // Like an open order but without the order id, client id and the
+        // fields that only matter while the order is working.
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            let mut f = Fields(seq);
+            let mut order = Order::default();
+
+            let contract = f.next()?;
+            order.action = f.next()?;
+            order.total_quantity = f.next()?;
+            order.order_type = f.next()?;
+            order.lmt_price = price(f.next()?);
+            order.aux_price = price(f.next()?);
+            order.tif = f.next()?;
+            order.oca_group = f.next()?;
+            order.account = f.next()?;
+            order.open_close = f.next()?;
+            f.skip(1)?; //origin
+            order.order_ref = f.next()?;
+            order.perm_id = f.next()?;
+            order.outside_rth = f.next()?;
+            order.hidden = f.next()?;
+            f.skip(1)?; //discretionary_amt
+            order.good_after_time = f.next()?;
+            f.skip(4)?; //fa group, method, percentage and profile
+            order.model_code = f.next()?;
+            order.good_till_date = f.next()?;
+            f.skip(11)?; //rule80a .. stock_range_upper, there's no auction_strategy
+            f.skip(2)?; //display_size, sweep_to_fill
+            order.all_or_none = f.next()?;
+            f.skip(1)?; //min_qty
+            order.oca_type = f.next()?;
+            f.skip(3)?; //trigger_method, volatility, volatility_type
+            f.skip_vol_order_params(5)?;
+            order.trail_stop_price = price(f.next()?);
+            order.trailing_percent = unset(f.next()?);
+            f.skip(1)?; //combo_legs_description
+            f.skip_combo_and_scale_params()?;
+            f.skip(2)?; //clearing_account, clearing_intent
+            f.read_not_held_and_algo(&mut order)?;
+            f.skip(1)?; //solicited
+            let status = f.next()?;
+            f.skip_randomize_and_conditions(&order)?;
+            f.skip(2)?; //trail_stop_price, lmt_price_offset
+            f.skip(4)?; //cash_qty .. auto_cancel_date
+            let filled_quantity = unset(f.next()?);
+            f.skip(6)?; //ref_futures_con_id .. parent_perm_id
+
+            let order_state = CompletedOrderState {
+                status,
+                filled_quantity,
+                completed_time: f.next()?,
+                completed_status: f.next()?,
+            };
+
+            Ok((contract, order, order_state))
+        }
+    }
+
+    pub fn deserialize_completed<'de, D>(deserializer: D) -> Result<CompletedOrder<'de>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(usize::MAX, CompletedOrderVisitor)
+    }
+}
+
+#[allow(dead_code, clippy::large_enum_variant)]
 #[derive(Deserialize, PartialEq, Debug)]
 pub enum TWSIncommingMessageImpl<'a> {
     #[serde(rename = "1")]
@@ -342,7 +1158,7 @@ pub enum TWSIncommingMessageImpl<'a> {
     #[serde(rename = "3")]
     OrderStatus {
         id: i32,
-        status: &'a str,
+        status: InnerOrderStatus,
         filled_amount: f64,
         remaining_amount: f64,
         avg_fill_price: Currency,
@@ -360,12 +1176,12 @@ pub enum TWSIncommingMessageImpl<'a> {
         code: i32,
         msg: &'a str,
     },
-    #[serde(rename = "5")]
+    #[serde(rename = "5", deserialize_with = "open_order::deserialize")]
     OpenOrder {
         order_id: i32,
         contract: OrderContract<'a>,
-        //order: Order,
-        //order_state: OrderState,
+        order: Order,
+        order_state: OrderState<'a>,
     },
     #[serde(rename = "6")]
     AccountValue {
@@ -376,9 +1192,19 @@ pub enum TWSIncommingMessageImpl<'a> {
         account_name: &'a str,
     },
     #[serde(rename = "7")]
-    PortfolioValue {},
+    PortfolioValue {
+        msg_version: i32,
+        contract: PortfolioContract<'a>,
+        position: f64,
+        market_price: f64,
+        market_value: f64,
+        average_cost: f64,
+        unrealized_pnl: f64,
+        realized_pnl: f64,
+        account_name: &'a str,
+    },
     #[serde(rename = "8")]
-    AccountUpdateTime {},
+    AccountUpdateTime { msg_version: i32, time: &'a str },
     #[serde(rename = "9")]
     NextValidId { msg_version: i32, order_id: i32 },
     #[serde(rename = "10")]
@@ -422,11 +1248,34 @@ pub enum TWSIncommingMessageImpl<'a> {
         real_expiration_date: &'a str,
     },
     #[serde(rename = "11")]
-    ExecutionData {},
+    ExecutionData {
+        req_id: i32,
+        order_id: i32,
+        contract: OrderContract<'a>,
+        execution: Execution,
+    },
     #[serde(rename = "12")]
-    MarketDepth {},
+    MarketDepth {
+        msg_version: i32,
+        req_id: i32,
+        position: i32,
+        operation: DepthOperation,
+        side: DepthSide,
+        price: Currency,
+        size: i64,
+    },
     #[serde(rename = "13")]
-    MarketDepthL2 {},
+    MarketDepthL2 {
+        msg_version: i32,
+        req_id: i32,
+        position: i32,
+        market_maker: &'a str,
+        operation: DepthOperation,
+        side: DepthSide,
+        price: Currency,
+        size: i64,
+        is_smart_depth: bool,
+    },
     #[serde(rename = "14")]
     NewsBulletins {},
     #[serde(rename = "15")]
@@ -492,17 +1341,24 @@ pub enum TWSIncommingMessageImpl<'a> {
     #[serde(rename = "49")]
     CurrentTime {},
     #[serde(rename = "50")]
-    RealTimeBars {},
+    RealTimeBars {
+        msg_version: i32,
+        req_id: i32,
+        bar: RealTimeBar,
+    },
     #[serde(rename = "51")]
     FundamentalData {},
     #[serde(rename = "52")]
-    ContractDataEnd,
+    ContractDataEnd { msg_version: i32, req_id: i32 },
     #[serde(rename = "53")]
-    OpenOrderEnd,
+    OpenOrderEnd { msg_version: i32 },
     #[serde(rename = "54")]
-    AccountDownloadEnd,
+    AccountDownloadEnd {
+        msg_version: i32,
+        account_name: &'a str,
+    },
     #[serde(rename = "55")]
-    ExecutionDataEnd,
+    ExecutionDataEnd { msg_version: i32, req_id: i32 },
     #[serde(rename = "56")]
     DeltaNeutralValidation {},
     #[serde(rename = "57")]
@@ -519,18 +1375,33 @@ pub enum TWSIncommingMessageImpl<'a> {
         exec_id: &'a str,
         commission: f64,
         currency: &'a str,
-        realized_pnl: f64,
-        yield_: f64,
-        yield_redemption_date: i32,
+        #[serde(deserialize_with = "unset_double")]
+        realized_pnl: Option<f64>,
+        #[serde(deserialize_with = "unset_double")]
+        yield_: Option<f64>,
+        yield_redemption_date: Option<i32>,
     },
     #[serde(rename = "61")]
-    Position {},
+    Position {
+        msg_version: i32,
+        account: &'a str,
+        contract: OrderContract<'a>,
+        position: f64,
+        average_cost: f64,
+    },
     #[serde(rename = "62")]
-    PositionEnd,
+    PositionEnd { msg_version: i32 },
     #[serde(rename = "63")]
-    AccountSummary {},
+    AccountSummary {
+        msg_version: i32,
+        req_id: i32,
+        account: &'a str,
+        tag: &'a str,
+        value: &'a str,
+        currency: &'a str,
+    },
     #[serde(rename = "64")]
-    AccountSummaryEnd,
+    AccountSummaryEnd { msg_version: i32, req_id: i32 },
     #[serde(rename = "65")]
     VerifyMessageApi {},
     #[serde(rename = "66")]
@@ -544,13 +1415,29 @@ pub enum TWSIncommingMessageImpl<'a> {
     #[serde(rename = "70")]
     VerifyAndAuthCompleted {},
     #[serde(rename = "71")]
-    PositionMulti {},
+    PositionMulti {
+        msg_version: i32,
+        req_id: i32,
+        account: &'a str,
+        contract: OrderContract<'a>,
+        position: f64,
+        average_cost: f64,
+        model_code: &'a str,
+    },
     #[serde(rename = "72")]
-    PositionMultiEnd,
+    PositionMultiEnd { msg_version: i32, req_id: i32 },
     #[serde(rename = "73")]
-    AccountUpdateMulti {},
+    AccountUpdateMulti {
+        msg_version: i32,
+        req_id: i32,
+        account: &'a str,
+        model_code: &'a str,
+        key: &'a str,
+        value: &'a str,
+        currency: &'a str,
+    },
     #[serde(rename = "74")]
-    AccountUpdateMultiEnd,
+    AccountUpdateMultiEnd { msg_version: i32, req_id: i32 },
     #[serde(rename = "75")]
     SecurityDefinitionOptionParameter {
         req_id: i32,
@@ -570,7 +1457,9 @@ pub enum TWSIncommingMessageImpl<'a> {
     #[serde(rename = "79")]
     SymbolSamples {},
     #[serde(rename = "80")]
-    MktDepthExchanges {},
+    MktDepthExchanges {
+        descriptions: Vec<DepthMktDataDescription>,
+    },
     #[serde(rename = "81")]
     TickReqParams {
         req_id: i32,
@@ -591,9 +1480,16 @@ pub enum TWSIncommingMessageImpl<'a> {
     #[serde(rename = "87")]
     HistoricalNewsEnd,
     #[serde(rename = "88")]
-    HeadTimestamp {},
+    HeadTimestamp {
+        req_id: i32,
+        #[serde(with = "chrono::serde::ts_seconds")]
+        head_timestamp: DateTime<Utc>,
+    },
     #[serde(rename = "89")]
-    HistogramData { req_id: i32, data: Vec<(f64, i64)> },
+    HistogramData {
+        req_id: i32,
+        data: Vec<(Currency, i64)>,
+    },
     #[serde(rename = "90")]
     HistoricalDataUpdate {
         req_id: i32,
@@ -607,21 +1503,60 @@ pub enum TWSIncommingMessageImpl<'a> {
     #[serde(rename = "93")]
     MarketRule {},
     #[serde(rename = "94")]
-    PnL {},
+    PnL {
+        req_id: i32,
+        #[serde(deserialize_with = "unset_double")]
+        daily_pnl: Option<f64>,
+        #[serde(deserialize_with = "unset_double")]
+        unrealized_pnl: Option<f64>,
+        #[serde(deserialize_with = "unset_double")]
+        realized_pnl: Option<f64>,
+    },
     #[serde(rename = "95")]
-    PnLSingle {},
+    PnLSingle {
+        req_id: i32,
+        position: f64,
+        #[serde(deserialize_with = "unset_double")]
+        daily_pnl: Option<f64>,
+        #[serde(deserialize_with = "unset_double")]
+        unrealized_pnl: Option<f64>,
+        #[serde(deserialize_with = "unset_double")]
+        realized_pnl: Option<f64>,
+        #[serde(deserialize_with = "unset_double")]
+        value: Option<f64>,
+    },
     #[serde(rename = "96")]
-    HistoricalTick {},
+    HistoricalTick {
+        req_id: i32,
+        ticks: Vec<HistoricalTickData>,
+        done: bool,
+    },
     #[serde(rename = "97")]
-    HistoricalTickBidAsk {},
+    HistoricalTickBidAsk {
+        req_id: i32,
+        ticks: Vec<HistoricalTickBidAskData>,
+        done: bool,
+    },
     #[serde(rename = "98")]
-    HistoricalTickLast {},
+    HistoricalTickLast {
+        req_id: i32,
+        ticks: Vec<HistoricalTickLastData>,
+        done: bool,
+    },
     #[serde(rename = "99")]
-    TickByTick {},
+    TickByTick {
+        req_id: i32,
+        #[serde(borrow)]
+        tick: TickByTick<'a>,
+    },
     #[serde(rename = "100")]
     OrderBound {},
-    #[serde(rename = "101")]
-    CompletedOrder {},
+    #[serde(rename = "101", deserialize_with = "open_order::deserialize_completed")]
+    CompletedOrder {
+        contract: OrderContract<'a>,
+        order: Order,
+        order_state: CompletedOrderState<'a>,
+    },
     #[serde(rename = "102")]
     CompletedOrdersEnd,
 }
@@ -646,39 +1581,112 @@ impl TWSIncommingMessageImpl<'_> {
             }
             TWSIncommingMessageImpl::HistoricalData { req_id, .. } => Some(*req_id),
             TWSIncommingMessageImpl::HistoricalDataUpdate { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::ContractData { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::ContractDataEnd { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::HistogramData { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::HeadTimestamp { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::RealTimeBars { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::TickByTick { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::MarketDepth { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::PositionMulti { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::AccountSummary { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::AccountSummaryEnd { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::AccountUpdateMulti { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::AccountUpdateMultiEnd { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::PositionMultiEnd { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::HistoricalTick { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::HistoricalTickBidAsk { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::HistoricalTickLast { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::MarketDepthL2 { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::PnL { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::ExecutionData { req_id, .. } if *req_id != -1 => Some(*req_id),
+            TWSIncommingMessageImpl::ExecutionDataEnd { req_id, .. } => Some(*req_id),
+            TWSIncommingMessageImpl::PnLSingle { req_id, .. } => Some(*req_id),
 
             _ => None,
         }
     }
-}
-
-#[derive(PartialEq, Debug)]
-pub struct TWSIncommingMessage {
-    data: DecodedMessage,
-    msg: TWSIncommingMessageImpl<'static>,
-}
+
+    /// The error TWS reported, if this is an error message.
+    pub fn to_error(&self) -> Option<Error> {
+        match self {
+            TWSIncommingMessageImpl::Error {
+                req_id, code, msg, ..
+            } => Some(Error::Tws {
+                req_id: *req_id,
+                code: *code,
+                message: (*msg).to_owned(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The order an order update belongs to. Errors about an order carry its
+    /// id in `req_id` and are not covered here.
+    pub fn get_order_id(&self) -> Option<i32> {
+        match self {
+            TWSIncommingMessageImpl::OrderStatus { id, .. } => Some(*id),
+            TWSIncommingMessageImpl::OpenOrder { order_id, .. } => Some(*order_id),
+            //executions of our own orders as they happen, requested ones carry a req_id
+            TWSIncommingMessageImpl::ExecutionData {
+                req_id: -1,
+                order_id,
+                ..
+            } => Some(*order_id),
+
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct TWSIncommingMessage {
+    //dropped before the frame it borrows from
+    msg: TWSIncommingMessageImpl<'static>,
+    data: TWSFrame,
+}
 
 impl TWSIncommingMessage {
     pub fn get_msg<'a>(&'a self) -> &'a TWSIncommingMessageImpl<'a> {
         &self.msg
     }
 
-    pub fn from_decoded_message(data: DecodedMessage) -> Result<TWSIncommingMessage> {
-        let mut de = Deserializer::from_msg(&data);
-        let parsed = TWSIncommingMessageImpl::deserialize(&mut de)?;
-        Result::Ok(TWSIncommingMessage {
-            data: data.clone(),
-            msg: unsafe {
-                //extend the lifetime to static but only hand out 'a references to it with get_msg()
+    pub fn from_decoded_message(data: TWSFrame) -> Result<TWSIncommingMessage> {
+        TWSIncommingMessage::from_decoded_message_with_version(data, versions::MAX_SERVER_VERSION)
+    }
+
+    /// Decodes a message sent by a server of `server_version`.
+    pub fn from_decoded_message_with_version(
+        data: TWSFrame,
+        server_version: i32,
+    ) -> Result<TWSIncommingMessage> {
+        TWSIncommingMessage::try_from_frame(data, server_version).map_err(|(e, _)| e)
+    }
+
+    /// Like `from_decoded_message_with_version` but hands the frame back on
+    /// failure so callers can report what couldn't be read.
+    pub fn try_from_frame(
+        data: TWSFrame,
+        server_version: i32,
+    ) -> std::result::Result<TWSIncommingMessage, (de_error::Error, TWSFrame)> {
+        let parsed = {
+            let mut de = Deserializer::from_msg_with_version(&data, server_version);
+            TWSIncommingMessageImpl::deserialize(&mut de).map(|parsed| unsafe {
+                //extend the lifetime to static but only hand out 'a references to it with get_msg(),
+                //the borrowed fields live in data's heap buffer which moving the frame doesn't move
                 std::mem::transmute::<TWSIncommingMessageImpl<'_>, TWSIncommingMessageImpl<'static>>(
                     parsed,
                 )
-            },
-        })
+            })
+        };
+        match parsed {
+            Ok(msg) => Ok(TWSIncommingMessage { data, msg }),
+            Err(e) => Err((e, data)),
+        }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone, Copy)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[allow(dead_code)]
 pub enum HistoricalDataType {
@@ -727,17 +1735,17 @@ pub mod BarSize {
         };
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
     pub struct Seconds<const N: u8>;
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
     pub struct Minutes<const N: u8>;
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
     pub struct Hours<const N: u8>;
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
     pub struct Day<const N: u8>;
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
     pub struct Week<const N: u8>;
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
     pub struct Month<const N: u8>;
 
     impl_valid_bar_size!(Seconds, "sec", 1, 5, 10, 15, 30);
@@ -748,6 +1756,207 @@ pub mod BarSize {
     impl_valid_bar_size!(Month, "month", 1);
 }
 
+mod place_order {
+    use super::Order;
+    use crate::tws::versions;
+    use serde::ser::{SerializeStructVariant, Serializer};
+    use twsapi::core::contract::Contract;
+
+    //without use_price_mgmt_algo
+    const FIELDS: usize = 109;
+
+    // Field order follows EClient.placeOrder for server versions 150 and
+    // 151. Order fields we don't model are sent with the defaults of IB's own
+    // clients.
+    pub fn serialize<S>(
+        order_id: &i32,
+        contract: &Contract,
+        order: &Order,
+        server_version: &i32,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let is_bag = contract.sec_type == "BAG";
+        let algo_fields = if order.algo_strategy.is_empty() {
+            0
+        } else {
+            1 + 2 * order.algo_params.len()
+        };
+        let bag_fields = if is_bag {
+            3 + 8 * contract.combo_legs.len()
+        } else {
+            0
+        };
+        let dn_fields = if contract.delta_neutral_contract.is_some() {
+            3
+        } else {
+            0
+        };
+        let price_mgmt_fields = (*server_version >= versions::PRICE_MGMT_ALGO) as usize;
+        let mut s = serializer.serialize_struct_variant(
+            "TWSOutgoingMessage",
+            3,
+            "3",
+            FIELDS + algo_fields + bag_fields + dn_fields + price_mgmt_fields,
+        )?;
+
+        s.serialize_field("order_id", order_id)?;
+
+        s.serialize_field("con_id", &contract.con_id)?;
+        s.serialize_field("symbol", &contract.symbol)?;
+        s.serialize_field("sec_type", &contract.sec_type)?;
+        s.serialize_field(
+            "last_trade_date_or_contract_month",
+            &contract.last_trade_date_or_contract_month,
+        )?;
+        s.serialize_field("strike", &contract.strike)?;
+        s.serialize_field("right", &contract.right)?;
+        s.serialize_field("multiplier", &contract.multiplier)?;
+        s.serialize_field("exchange", &contract.exchange)?;
+        s.serialize_field("primary_exchange", &contract.primary_exchange)?;
+        s.serialize_field("currency", &contract.currency)?;
+        s.serialize_field("local_symbol", &contract.local_symbol)?;
+        s.serialize_field("trading_class", &contract.trading_class)?;
+        s.serialize_field("sec_id_type", &contract.sec_id_type)?;
+        s.serialize_field("sec_id", &contract.sec_id)?;
+
+        s.serialize_field("action", &order.action)?;
+        s.serialize_field("total_quantity", &order.total_quantity)?;
+        s.serialize_field("order_type", &order.order_type)?;
+        s.serialize_field("lmt_price", &order.lmt_price)?;
+        s.serialize_field("aux_price", &order.aux_price)?;
+        s.serialize_field("tif", &order.tif)?;
+        s.serialize_field("oca_group", &order.oca_group)?;
+        s.serialize_field("account", &order.account)?;
+        s.serialize_field("open_close", &order.open_close)?;
+        s.serialize_field("origin", &0)?;
+        s.serialize_field("order_ref", &order.order_ref)?;
+        s.serialize_field("transmit", &order.transmit)?;
+        s.serialize_field("parent_id", &order.parent_id)?;
+        s.serialize_field("block_order", &false)?;
+        s.serialize_field("sweep_to_fill", &false)?;
+        s.serialize_field("display_size", &0)?;
+        s.serialize_field("trigger_method", &0)?;
+        s.serialize_field("outside_rth", &order.outside_rth)?;
+        s.serialize_field("hidden", &order.hidden)?;
+
+        if is_bag {
+            s.serialize_field("combo_legs_count", &contract.combo_legs.len())?;
+            for leg in contract.combo_legs.iter() {
+                s.serialize_field("con_id", &leg.con_id)?;
+                s.serialize_field("ratio", &leg.ratio)?;
+                s.serialize_field("action", &leg.action)?;
+                s.serialize_field("exchange", &leg.exchange)?;
+                s.serialize_field("open_close", &(leg.open_close as i32))?;
+                s.serialize_field("short_sale_slot", &leg.short_sale_slot)?;
+                s.serialize_field("designated_location", &leg.designated_location)?;
+                s.serialize_field("exempt_code", &leg.exempt_code)?;
+            }
+            s.serialize_field("order_combo_legs_count", &0)?;
+            s.serialize_field("smart_combo_routing_params_count", &0)?;
+        }
+
+        s.serialize_field("shares_allocation", "")?;
+        s.serialize_field("discretionary_amt", &0)?;
+        s.serialize_field("good_after_time", &order.good_after_time)?;
+        s.serialize_field("good_till_date", &order.good_till_date)?;
+        s.serialize_field("fa_group", "")?;
+        s.serialize_field("fa_method", "")?;
+        s.serialize_field("fa_percentage", "")?;
+        s.serialize_field("fa_profile", "")?;
+        s.serialize_field("model_code", &order.model_code)?;
+        s.serialize_field("short_sale_slot", &0)?;
+        s.serialize_field("designated_location", "")?;
+        s.serialize_field("exempt_code", &-1)?;
+        s.serialize_field("oca_type", &order.oca_type)?;
+        s.serialize_field("rule80a", "")?;
+        s.serialize_field("settling_firm", "")?;
+        s.serialize_field("all_or_none", &order.all_or_none)?;
+        s.serialize_field("min_qty", &None::<i32>)?;
+        s.serialize_field("percent_offset", &None::<f64>)?;
+        s.serialize_field("e_trade_only", &false)?;
+        s.serialize_field("firm_quote_only", &false)?;
+        s.serialize_field("nbbo_price_cap", &None::<f64>)?;
+        s.serialize_field("auction_strategy", &0)?;
+        s.serialize_field("starting_price", &None::<f64>)?;
+        s.serialize_field("stock_ref_price", &None::<f64>)?;
+        s.serialize_field("delta", &None::<f64>)?;
+        s.serialize_field("stock_range_lower", &None::<f64>)?;
+        s.serialize_field("stock_range_upper", &None::<f64>)?;
+        s.serialize_field("override_percentage_constraints", &false)?;
+        s.serialize_field("volatility", &None::<f64>)?;
+        s.serialize_field("volatility_type", &None::<i32>)?;
+        s.serialize_field("delta_neutral_order_type", "")?;
+        s.serialize_field("delta_neutral_aux_price", &None::<f64>)?;
+        s.serialize_field("continuous_update", &false)?;
+        s.serialize_field("reference_price_type", &None::<i32>)?;
+        s.serialize_field("trail_stop_price", &order.trail_stop_price)?;
+        s.serialize_field("trailing_percent", &order.trailing_percent)?;
+        s.serialize_field("scale_init_level_size", &None::<i32>)?;
+        s.serialize_field("scale_subs_level_size", &None::<i32>)?;
+        s.serialize_field("scale_price_increment", &None::<f64>)?;
+        s.serialize_field("scale_table", "")?;
+        s.serialize_field("active_start_time", "")?;
+        s.serialize_field("active_stop_time", "")?;
+        s.serialize_field("hedge_type", "")?;
+        s.serialize_field("opt_out_smart_routing", &false)?;
+        s.serialize_field("clearing_account", "")?;
+        s.serialize_field("clearing_intent", "")?;
+        s.serialize_field("not_held", &order.not_held)?;
+
+        match &contract.delta_neutral_contract {
+            Some(dn) => {
+                s.serialize_field("delta_neutral_contract", &true)?;
+                s.serialize_field("con_id", &dn.con_id)?;
+                s.serialize_field("delta", &dn.delta)?;
+                s.serialize_field("price", &dn.price)?;
+            }
+            None => s.serialize_field("delta_neutral_contract", &false)?,
+        }
+
+        s.serialize_field("algo_strategy", &order.algo_strategy)?;
+        if !order.algo_strategy.is_empty() {
+            s.serialize_field("algo_params_count", &order.algo_params.len())?;
+            for (tag, value) in order.algo_params.iter() {
+                s.serialize_field("tag", tag)?;
+                s.serialize_field("value", value)?;
+            }
+        }
+        s.serialize_field("algo_id", "")?;
+        s.serialize_field("what_if", &order.what_if)?;
+        s.serialize_field("misc_options", "")?;
+        s.serialize_field("solicited", &false)?;
+        s.serialize_field("randomize_size", &false)?;
+        s.serialize_field("randomize_price", &false)?;
+        s.serialize_field("conditions_count", &0)?;
+        s.serialize_field("adjusted_order_type", "")?;
+        s.serialize_field("trigger_price", &None::<f64>)?;
+        s.serialize_field("lmt_price_offset", &None::<f64>)?;
+        s.serialize_field("adjusted_stop_price", &None::<f64>)?;
+        s.serialize_field("adjusted_stop_limit_price", &None::<f64>)?;
+        s.serialize_field("adjusted_trailing_amount", &None::<f64>)?;
+        s.serialize_field("adjustable_trailing_unit", &0)?;
+        s.serialize_field("ext_operator", "")?;
+        s.serialize_field("soft_dollar_tier_name", "")?;
+        s.serialize_field("soft_dollar_tier_value", "")?;
+        s.serialize_field("cash_qty", &None::<f64>)?;
+        s.serialize_field("mifid2_decision_maker", "")?;
+        s.serialize_field("mifid2_decision_algo", "")?;
+        s.serialize_field("mifid2_execution_trader", "")?;
+        s.serialize_field("mifid2_execution_algo", "")?;
+        s.serialize_field("dont_use_auto_price_for_hedge", &false)?;
+        s.serialize_field("is_oms_container", &false)?;
+        s.serialize_field("discretionary_up_to_limit_price", &false)?;
+        if *server_version >= versions::PRICE_MGMT_ALGO {
+            s.serialize_field("use_price_mgmt_algo", &None::<bool>)?;
+        }
+
+        s.end()
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub enum TWSOutgoingMessage {
@@ -759,18 +1968,38 @@ pub enum TWSOutgoingMessage {
     },
     #[serde(rename = "2")]
     CancelMarketData { version: i32, req_id: i32 },
-    #[serde(rename = "3")]
-    PlaceOrder {},
+    #[serde(rename = "3", serialize_with = "place_order::serialize")]
+    PlaceOrder {
+        order_id: i32,
+        contract: contract::Contract,
+        order: Order,
+        //the negotiated one, the layout depends on it
+        server_version: i32,
+    },
     #[serde(rename = "4")]
-    CancelOrder {},
+    CancelOrder { version: i32, order_id: i32 },
     #[serde(rename = "5")]
-    RequestOpenOrders {},
+    RequestOpenOrders { version: i32 },
     #[serde(rename = "6")]
-    RequestAccountData {},
+    RequestAccountData {
+        version: i32,
+        subscribe: bool,
+        account_code: String,
+    },
     #[serde(rename = "7")]
-    RequestExecutions {},
+    RequestExecutions {
+        version: i32,
+        req_id: i32,
+        client_id: i32,
+        account: String,
+        time: String,
+        symbol: String,
+        sec_type: String,
+        exchange: String,
+        side: String,
+    },
     #[serde(rename = "8")]
-    RequestIds {},
+    RequestIds { version: i32, num_ids: i32 },
     #[serde(rename = "9")]
     RequestContractData {
         version: i32,
@@ -793,9 +2022,32 @@ pub enum TWSOutgoingMessage {
         //combo_legs_description: &'a str,
     },
     #[serde(rename = "10")]
-    RequestMarketDepth {},
+    RequestMarketDepth {
+        version: i32,
+        req_id: i32,
+        con_id: i32,
+        symbol: String,
+        sec_type: String,
+        last_trade_date_or_contract_month: String,
+        strike: f64,
+        right: String,
+        multiplier: String,
+        exchange: String,
+        primary_exchange: String,
+        currency: String,
+        local_symbol: String,
+        trading_class: String,
+
+        num_rows: i32,
+        is_smart_depth: bool,
+        market_depth_options: (),
+    },
     #[serde(rename = "11")]
-    CancelMarketDepth {},
+    CancelMarketDepth {
+        version: i32,
+        req_id: i32,
+        is_smart_depth: bool,
+    },
     #[serde(rename = "12")]
     RequestNewsBulletins {},
     #[serde(rename = "13")]
@@ -803,9 +2055,9 @@ pub enum TWSOutgoingMessage {
     #[serde(rename = "14")]
     ChangeServerLog {},
     #[serde(rename = "15")]
-    RequestAutoOpenOrders {},
+    RequestAutoOpenOrders { version: i32, auto_bind: bool },
     #[serde(rename = "16")]
-    RequestAllOpenOrders {},
+    RequestAllOpenOrders { version: i32 },
     #[serde(rename = "17")]
     RequestManagedAccounts {},
     #[serde(rename = "18")]
@@ -849,13 +2101,33 @@ pub enum TWSOutgoingMessage {
     #[serde(rename = "24")]
     RequestScannerParameters {},
     #[serde(rename = "25")]
-    CancelHistoricalData {},
+    CancelHistoricalData { version: i32, req_id: i32 },
     #[serde(rename = "49")]
     RequestCurrentTime {},
     #[serde(rename = "50")]
-    RequestRealTimeBars {},
+    RequestRealTimeBars {
+        version: i32,
+        req_id: i32,
+        con_id: i32,
+        symbol: String,
+        sec_type: String,
+        last_trade_date_or_contract_month: String,
+        strike: f64,
+        right: String,
+        multiplier: String,
+        exchange: String,
+        primary_exchange: String,
+        currency: String,
+        local_symbol: String,
+        trading_class: String,
+
+        bar_size: i32, //only 5 seconds is supported
+        what_to_show: HistoricalDataType,
+        use_regular_trading_hours: bool,
+        real_time_bars_options: (),
+    },
     #[serde(rename = "51")]
-    CancelRealTimeBars {},
+    CancelRealTimeBars { version: i32, req_id: i32 },
     #[serde(rename = "52")]
     RequestFundamentalData {},
     #[serde(rename = "53")]
@@ -873,13 +2145,18 @@ pub enum TWSOutgoingMessage {
     #[serde(rename = "59")]
     RequestMarketDataType {},
     #[serde(rename = "61")]
-    RequestPositions {},
+    RequestPositions { version: i32 },
     #[serde(rename = "62")]
-    RequestAccountSummary {},
+    RequestAccountSummary {
+        version: i32,
+        req_id: i32,
+        group: String,
+        tags: String,
+    },
     #[serde(rename = "63")]
-    CancelAccountSummary {},
+    CancelAccountSummary { version: i32, req_id: i32 },
     #[serde(rename = "64")]
-    CancelPositions {},
+    CancelPositions { version: i32 },
     #[serde(rename = "65")]
     VerifyRequest {},
     #[serde(rename = "66")]
@@ -899,13 +2176,24 @@ pub enum TWSOutgoingMessage {
     #[serde(rename = "73")]
     VerifyAndAuthMessage {},
     #[serde(rename = "74")]
-    RequestPositionsMulti {},
+    RequestPositionsMulti {
+        version: i32,
+        req_id: i32,
+        account: String,
+        model_code: String,
+    },
     #[serde(rename = "75")]
-    CancelPositionsMulti {},
+    CancelPositionsMulti { version: i32, req_id: i32 },
     #[serde(rename = "76")]
-    RequestAccountUpdatesMulti {},
+    RequestAccountUpdatesMulti {
+        version: i32,
+        req_id: i32,
+        account: String,
+        model_code: String,
+        ledger_and_nlv: bool,
+    },
     #[serde(rename = "77")]
-    CancelAccountUpdatesMulti {},
+    CancelAccountUpdatesMulti { version: i32, req_id: i32 },
     #[serde(rename = "78")]
     RequestSecurityDefinitionOptionalParameters {
         req_id: i32,
@@ -931,7 +2219,26 @@ pub enum TWSOutgoingMessage {
     #[serde(rename = "86")]
     RequestHistoricalNews {},
     #[serde(rename = "87")]
-    RequestHeadTimestamp {},
+    RequestHeadTimestamp {
+        req_id: i32,
+        con_id: i32,
+        symbol: String,
+        sec_type: String,
+        last_trade_date_or_contract_month: String,
+        strike: f64,
+        right: String,
+        multiplier: String,
+        exchange: String,
+        primary_exchange: String,
+        currency: String,
+        local_symbol: String,
+        trading_class: String,
+        include_expired: bool,
+        use_regular_trading_hours: bool,
+        what_to_show: HistoricalDataType,
+        //2 for epoch seconds
+        format_date: i32,
+    },
     #[serde(rename = "88")]
     RequestHistogramData {
         req_id: i32,
@@ -952,33 +2259,91 @@ pub enum TWSOutgoingMessage {
         period: String,
     },
     #[serde(rename = "89")]
-    CancelHistogramData {},
+    CancelHistogramData { req_id: i32 },
     #[serde(rename = "90")]
-    CancelHeadTimestamp {},
+    CancelHeadTimestamp { req_id: i32 },
     #[serde(rename = "91")]
     RequestMarketRule {},
     #[serde(rename = "92")]
-    ReqPnL {},
+    ReqPnL {
+        req_id: i32,
+        account: String,
+        model_code: String,
+    },
     #[serde(rename = "93")]
-    CancelPnL {},
+    CancelPnL { req_id: i32 },
     #[serde(rename = "94")]
-    ReqPnLSingle {},
+    ReqPnLSingle {
+        req_id: i32,
+        account: String,
+        model_code: String,
+        con_id: i32,
+    },
     #[serde(rename = "95")]
-    CancelPnLSingle {},
+    CancelPnLSingle { req_id: i32 },
     #[serde(rename = "96")]
-    ReqHistoricalTicks {},
+    ReqHistoricalTicks {
+        req_id: i32,
+        con_id: i32,
+        symbol: String,
+        sec_type: String,
+        last_trade_date_or_contract_month: String,
+        strike: f64,
+        right: String,
+        multiplier: String,
+        exchange: String,
+        primary_exchange: String,
+        currency: String,
+        local_symbol: String,
+        trading_class: String,
+        include_expired: bool,
+
+        start_date_time: String,
+        end_date_time: String,
+        number_of_ticks: i32,
+        what_to_show: HistoricalDataType,
+        use_regular_trading_hours: bool,
+        ignore_size: bool,
+        misc_options: (),
+    },
     #[serde(rename = "97")]
-    ReqTickByTickData {},
+    ReqTickByTickData {
+        req_id: i32,
+        con_id: i32,
+        symbol: String,
+        sec_type: String,
+        last_trade_date_or_contract_month: String,
+        strike: f64,
+        right: String,
+        multiplier: String,
+        exchange: String,
+        primary_exchange: String,
+        currency: String,
+        local_symbol: String,
+        trading_class: String,
+
+        tick_type: TickByTickType,
+        number_of_ticks: i32,
+        ignore_size: bool,
+    },
     #[serde(rename = "98")]
-    CancelTickByTickData {},
+    CancelTickByTickData { req_id: i32 },
     #[serde(rename = "99")]
-    ReqCompletedOrders {},
+    ReqCompletedOrders { api_only: bool },
 }
 
 #[cfg(test)]
-mod tests {
-    use super::super::serde::de::Deserializer;
-    use super::{HistoricalBarData, TWSIncommingMessageImpl};
+pub(crate) mod tests {
+    use super::super::{
+        serde::{de::Deserializer, ser},
+        versions,
+    };
+    use super::{
+        AccountSummaryTag, Action, CompletedOrderState, DepthMktDataDescription, DepthOperation,
+        DepthSide, HistoricalBarData, HistoricalTickBidAskData, HistoricalTickLastData,
+        InnerOrderStatus, Order, OrderContract, OrderState, RealTimeBar, TWSIncommingMessageImpl,
+        TWSOutgoingMessage, TickAttributeBidAsk, TickAttributeLast, TickByTick, TimeInForce,
+    };
     use bytes::Bytes;
     use chrono::{self, TimeZone, Utc};
     use serde::Deserialize;
@@ -1037,6 +2402,860 @@ mod tests {
             TWSIncommingMessageImpl::deserialize(&mut de)
         );
     }
+
+    #[test]
+    fn can_deser_historical_data_update_without_wap() {
+        let msg = vec![
+            "90".into(),
+            "0".into(),
+            "3".into(),
+            "20210419  15:56:15".into(),
+            "10.30".into(),
+            "10.20".into(),
+            "10.35".into(),
+            "10.00".into(),
+            "".into(),
+            "100".into(),
+        ];
+        let mut de = Deserializer::from_msg(&msg);
+
+        //the empty wap is consumed, so volume is read from its own field
+        match TWSIncommingMessageImpl::deserialize(&mut de).unwrap() {
+            TWSIncommingMessageImpl::HistoricalDataUpdate { bar, .. } => {
+                assert_eq!((None, Some(100)), (bar.wap, bar.volume));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn can_deser_tick_req_params() {
+        let msg = vec![
+            "81".into(),
+            "3".into(),
+            "".into(),
+            "9c0001".into(),
+            "1".into(),
+        ];
+        let mut de = Deserializer::from_msg(&msg);
+
+        //an empty min_tick reads as 0 and 1 is true
+        assert_eq!(
+            Ok(TWSIncommingMessageImpl::TickReqParams {
+                req_id: 3,
+                min_tick: 0.0,
+                bbo_exchange: "9c0001",
+                snapshot_permssion: true,
+            }),
+            TWSIncommingMessageImpl::deserialize(&mut de)
+        );
+    }
+
+    #[test]
+    fn can_deser_contract_data_end() {
+        let msg = vec!["52".into(), "1".into(), "1073741824".into()];
+        let mut de = Deserializer::from_msg(&msg);
+
+        let parsed = TWSIncommingMessageImpl::deserialize(&mut de).unwrap();
+        assert_eq!(
+            TWSIncommingMessageImpl::ContractDataEnd {
+                msg_version: 1,
+                req_id: 1 << 30
+            },
+            parsed
+        );
+        assert_eq!(Some(1 << 30), parsed.get_req_id());
+    }
+
+    #[test]
+    fn can_deser_real_time_bars() {
+        let msg = "50|3|1073741825|1618847775|134.05|134.10|134.00|134.08|1200|134.061|17"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        let parsed = TWSIncommingMessageImpl::deserialize(&mut de).unwrap();
+        assert_eq!(
+            TWSIncommingMessageImpl::RealTimeBars {
+                msg_version: 3,
+                req_id: (1 << 30) + 1,
+                bar: RealTimeBar {
+                    time: Utc.timestamp_opt(1618847775, 0).unwrap(),
+                    open: dec!(134.05),
+                    high: dec!(134.10),
+                    low: dec!(134.00),
+                    close: dec!(134.08),
+                    volume: 1200,
+                    wap: dec!(134.061),
+                    count: 17,
+                }
+            },
+            parsed
+        );
+        assert_eq!(Some((1 << 30) + 1), parsed.get_req_id());
+    }
+
+    #[test]
+    fn can_deser_tick_by_tick_last() {
+        let msg = "99|1073741826|1|1618847775|134.05|300|2|ARCA|"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::TickByTick {
+                req_id: (1 << 30) + 2,
+                tick: TickByTick::Last {
+                    time: Utc.timestamp_opt(1618847775, 0).unwrap(),
+                    price: dec!(134.05),
+                    size: 300,
+                    attrib: TickAttributeLast::UNREPORTED,
+                    exchange: "ARCA",
+                    special_conditions: "",
+                }
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_tick_by_tick_bid_ask() {
+        let msg = "99|7|3|1618847775|134.04|134.06|100|200|3"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::TickByTick {
+                req_id: 7,
+                tick: TickByTick::BidAsk {
+                    time: Utc.timestamp_opt(1618847775, 0).unwrap(),
+                    bid_price: dec!(134.04),
+                    ask_price: dec!(134.06),
+                    bid_size: 100,
+                    ask_size: 200,
+                    attrib: TickAttributeBidAsk::all(),
+                }
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_market_depth_l2() {
+        let msg = "13|1|7|2|NSDQ|1|0|134.05|300|1"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::MarketDepthL2 {
+                msg_version: 1,
+                req_id: 7,
+                position: 2,
+                market_maker: "NSDQ",
+                operation: DepthOperation::Update,
+                side: DepthSide::Ask,
+                price: dec!(134.05),
+                size: 300,
+                is_smart_depth: true,
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_mkt_depth_exchanges() {
+        let msg = "80|2|ISLAND|STK|NASDAQ|Deep2|1|IDEALPRO|CASH||Deep|"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::MktDepthExchanges {
+                descriptions: vec![
+                    DepthMktDataDescription {
+                        exchange: "ISLAND".to_owned(),
+                        sec_type: "STK".to_owned(),
+                        listing_exchange: "NASDAQ".to_owned(),
+                        service_data_type: "Deep2".to_owned(),
+                        agg_group: Some(1),
+                    },
+                    DepthMktDataDescription {
+                        exchange: "IDEALPRO".to_owned(),
+                        sec_type: "CASH".to_owned(),
+                        listing_exchange: "".to_owned(),
+                        service_data_type: "Deep".to_owned(),
+                        agg_group: None,
+                    },
+                ]
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_historical_ticks_last() {
+        let msg = "98|7|2|1618847775|0|134.05|100|ARCA||1618847776|2|134.06|5|NYSE|I|1"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::HistoricalTickLast {
+                req_id: 7,
+                ticks: vec![
+                    HistoricalTickLastData {
+                        time: Utc.timestamp_opt(1618847775, 0).unwrap(),
+                        attrib: TickAttributeLast::empty(),
+                        price: dec!(134.05),
+                        size: 100,
+                        exchange: "ARCA".to_owned(),
+                        special_conditions: "".to_owned(),
+                    },
+                    HistoricalTickLastData {
+                        time: Utc.timestamp_opt(1618847776, 0).unwrap(),
+                        attrib: TickAttributeLast::UNREPORTED,
+                        price: dec!(134.06),
+                        size: 5,
+                        exchange: "NYSE".to_owned(),
+                        special_conditions: "I".to_owned(),
+                    },
+                ],
+                done: true,
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_historical_ticks_bid_ask() {
+        let msg = "97|7|1|1618847775|1|134.04|134.06|100|200|0"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::HistoricalTickBidAsk {
+                req_id: 7,
+                ticks: vec![HistoricalTickBidAskData {
+                    time: Utc.timestamp_opt(1618847775, 0).unwrap(),
+                    attrib: TickAttributeBidAsk::ASK_PAST_HIGH,
+                    bid_price: dec!(134.04),
+                    ask_price: dec!(134.06),
+                    bid_size: 100,
+                    ask_size: 200,
+                }],
+                done: false,
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_position() {
+        let msg = "61|3|DU123|265598|AAPL|STK||0|||NASDAQ|USD|AAPL|NMS|100|134.5"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::Position {
+                msg_version: 3,
+                account: "DU123",
+                contract: OrderContract {
+                    con_id: 265598,
+                    symbol: "AAPL",
+                    sec_type: "STK",
+                    last_trade_date_or_contract_month: "",
+                    strike: dec!(0),
+                    right: "",
+                    multiplier: "",
+                    exchange: "NASDAQ",
+                    currency: "USD",
+                    local_symbol: "AAPL",
+                    trading_class: "NMS",
+                },
+                position: 100.0,
+                average_cost: 134.5,
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_account_summary() {
+        let msg = "63|1|1073741824|DU123|NetLiquidation|100000.50|USD"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::AccountSummary {
+                msg_version: 1,
+                req_id: 1073741824,
+                account: "DU123",
+                tag: "NetLiquidation",
+                value: "100000.50",
+                currency: "USD",
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+        assert_eq!(
+            Some(AccountSummaryTag::NetLiquidation),
+            AccountSummaryTag::from_key("NetLiquidation")
+        );
+    }
+
+    #[test]
+    fn can_deser_pnl_single_with_unset_values() {
+        let msg = "95|1073741824|100|-25.5|1.7976931348623157E308|0|13450"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::PnLSingle {
+                req_id: 1073741824,
+                position: 100.0,
+                daily_pnl: Some(-25.5),
+                unrealized_pnl: None,
+                realized_pnl: Some(0.0),
+                value: Some(13450.0),
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_execution_data() {
+        let msg = "11|1073741824|7|265598|AAPL|STK||0|||ISLAND|USD|AAPL|NMS|0000e0d5.5d3f4a3a.01.01|20210422  10:30:00|DU123|ISLAND|BOT|100|134.5|1234|0|0|100|134.5|||||2"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        match TWSIncommingMessageImpl::deserialize(&mut de).unwrap() {
+            TWSIncommingMessageImpl::ExecutionData {
+                req_id,
+                order_id,
+                contract,
+                execution,
+            } => {
+                assert_eq!(1073741824, req_id);
+                assert_eq!(7, order_id);
+                assert_eq!(265598, contract.con_id);
+                assert_eq!("0000e0d5.5d3f4a3a.01.01", execution.exec_id);
+                assert_eq!("BOT", execution.side);
+                assert_eq!(100.0, execution.shares);
+                assert_eq!(None, execution.ev_multiplier);
+                assert_eq!(2, execution.last_liquidity);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn can_deser_commissions_report() {
+        let msg =
+            "59|1|0000e0d5.5d3f4a3a.01.01|1.0|USD|1.7976931348623157E308|1.7976931348623157E308|"
+                .split('|')
+                .map(|s| Bytes::from(s.to_owned()))
+                .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::CommissionsReport {
+                msg_version: 1,
+                exec_id: "0000e0d5.5d3f4a3a.01.01",
+                commission: 1.0,
+                currency: "USD",
+                realized_pnl: None,
+                yield_: None,
+                yield_redemption_date: None,
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_ser_request_executions() {
+        let msg = TWSOutgoingMessage::RequestExecutions {
+            version: 3,
+            req_id: 1,
+            client_id: 0,
+            account: "DU123".to_owned(),
+            time: "".to_owned(),
+            symbol: "".to_owned(),
+            sec_type: "".to_owned(),
+            exchange: "".to_owned(),
+            side: "BUY".to_owned(),
+        };
+        let mut buf = Vec::new();
+        ser::to_writer(&msg, &mut buf).unwrap();
+
+        assert_eq!(b"7\03\01\00\0DU123\0\0\0\0\0BUY\0".to_vec(), buf);
+    }
+
+    #[test]
+    fn can_deser_head_timestamp_and_histogram() {
+        let msg = "88|1073741824|1262304000"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::HeadTimestamp {
+                req_id: 1073741824,
+                head_timestamp: Utc.timestamp_opt(1262304000, 0).unwrap(),
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+
+        let msg = "89|1073741825|2|134.5|1200|134.75|300"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        assert_eq!(
+            TWSIncommingMessageImpl::HistogramData {
+                req_id: 1073741825,
+                data: vec![(dec!(134.5), 1200), (dec!(134.75), 300)],
+            },
+            TWSIncommingMessageImpl::deserialize(&mut de).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_deser_order_status() {
+        let msg = "3|7|Filled|100|0|201.5|1234|0|201.5|0||0"
+            .split('|')
+            .map(|s| Bytes::from(s.to_owned()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        let parsed = TWSIncommingMessageImpl::deserialize(&mut de).unwrap();
+        match parsed {
+            TWSIncommingMessageImpl::OrderStatus {
+                id,
+                status,
+                filled_amount,
+                remaining_amount,
+                avg_fill_price,
+                ..
+            } => {
+                assert_eq!(7, id);
+                assert_eq!(InnerOrderStatus::Filled, status);
+                assert!(status.is_done());
+                assert_eq!(100.0, filled_amount);
+                assert_eq!(0.0, remaining_amount);
+                assert_eq!(dec!(201.5), avg_fill_price);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(Some(7), parsed.get_order_id());
+    }
+
+    #[test]
+    fn can_deser_completed_order() {
+        let max = "1.7976931348623157E308";
+        let fields = [
+            "101",
+            "265598",
+            "AAPL",
+            "STK",
+            "",
+            "0",
+            "?",
+            "",
+            "SMART",
+            "USD",
+            "AAPL",
+            "NMS", //contract
+            "SELL",
+            "100",
+            "LMT",
+            "151.5",
+            "0.0",
+            "GTC",
+            "",
+            "DU123",
+            "C",
+            "0",
+            "",
+            "1235",
+            "0",
+            "0",
+            "0",
+            "", //action .. good_after_time
+            "",
+            "",
+            "",
+            "",
+            "",
+            "", //fa params, model code, good till date
+            "",
+            max,
+            "",
+            "0",
+            "",
+            "-1",
+            max,
+            max,
+            max,
+            max,
+            max, //rule80a .. stock_range_upper
+            "0",
+            "0",
+            "0",
+            "",
+            "3", //display_size .. oca_type
+            "0",
+            max,
+            "0",
+            "",
+            max,
+            "0",
+            "0", //trigger_method .. reference_price_type
+            max,
+            max,
+            "", //trail params, combo legs description
+            "0",
+            "0",
+            "0", //combo legs, order combo legs, smart combo routing params
+            "",
+            "",
+            max, //scale
+            "",
+            "",
+            "",
+            "0",
+            "0", //hedge type .. delta neutral
+            "",
+            "0", //algo strategy, solicited
+            "Cancelled",
+            "0",
+            "0",
+            "0", //randomize, conditions
+            max,
+            max,
+            max,
+            "0",
+            "0",
+            "",
+            "0", //trail_stop_price .. filled_quantity
+            "0",
+            "0",
+            "",
+            "0",
+            "0",
+            max, //ref_futures_con_id .. parent_perm_id
+            "20210422 10:35:00",
+            "Cancelled by Trader",
+        ];
+        let msg = fields
+            .iter()
+            .map(|s| Bytes::from(s.to_string()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        match TWSIncommingMessageImpl::deserialize(&mut de).unwrap() {
+            TWSIncommingMessageImpl::CompletedOrder {
+                contract,
+                order,
+                order_state,
+            } => {
+                assert_eq!(265598, contract.con_id);
+                assert_eq!(Action::Sell, order.action);
+                assert_eq!(Some(dec!(151.5)), order.lmt_price);
+                assert_eq!(TimeInForce::Gtc, order.tif);
+                assert_eq!(1235, order.perm_id);
+                assert_eq!(3, order.oca_type);
+                assert_eq!(
+                    CompletedOrderState {
+                        status: InnerOrderStatus::Cancelled,
+                        filled_quantity: Some(0.0),
+                        completed_time: "20210422 10:35:00",
+                        completed_status: "Cancelled by Trader",
+                    },
+                    order_state
+                );
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    const MAX: &str = "1.7976931348623157E308";
+
+    /// An `OpenOrder` as sent by server version 151, 150 leaves out the last
+    /// field.
+    pub(crate) const OPEN_ORDER: &[&str] = &[
+        "5",
+        "7", //order id
+        "265598",
+        "AAPL",
+        "STK",
+        "",
+        "0",
+        "?",
+        "",
+        "SMART",
+        "USD",
+        "AAPL",
+        "NMS", //contract
+        "BUY",
+        "100",
+        "LMT",
+        "150.25",
+        "0.0",
+        "DAY",
+        "",
+        "DU123",
+        "O",
+        "0",
+        "my ref",
+        "0",
+        "1234",
+        "0",
+        "0",
+        "0",
+        "", //action .. good_after_time
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "", //fa params, model code, good till date
+        "",
+        MAX,
+        "",
+        "0",
+        "",
+        "-1",
+        "0",
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX, //rule80a .. stock_range_upper
+        "0",
+        "0",
+        "0",
+        "0",
+        "",
+        "3",
+        "0",
+        "0",
+        MAX,
+        "0", //display_size .. parent_id
+        "0",
+        MAX,
+        "0",
+        "",
+        MAX,
+        "0",
+        "0", //trigger_method .. reference_price_type
+        MAX,
+        MAX,
+        MAX,
+        "",
+        "", //trail params, basis points, combo legs description
+        "0",
+        "0",
+        "0", //combo legs, order combo legs, smart combo routing params
+        "",
+        "",
+        MAX, //scale
+        "",
+        "0",
+        "",
+        "",
+        "0",
+        "0", //hedge type .. delta neutral
+        "",
+        "0",
+        "0", //algo strategy, solicited, what_if
+        "PreSubmitted",
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        "",
+        "",
+        "0",
+        "0",
+        "0", //randomize, conditions
+        "None",
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        MAX,
+        "0", //adjusted order params
+        "",
+        "",
+        "", //soft dollar tier
+        MAX,
+        "0",
+        "0",
+        "0",
+        "", //cash_qty .. use_price_mgmt_algo
+    ];
+
+    #[test]
+    fn can_deser_open_order() {
+        let msg = OPEN_ORDER
+            .iter()
+            .map(|s| Bytes::from(s.to_string()))
+            .collect::<Vec<_>>();
+        let mut de = Deserializer::from_msg(&msg);
+
+        let parsed = TWSIncommingMessageImpl::deserialize(&mut de).unwrap();
+        let mut order = Order::limit(Action::Buy, 100.0, dec!(150.25));
+        order.account = "DU123".to_owned();
+        order.open_close = "O".to_owned();
+        order.order_ref = "my ref".to_owned();
+        order.perm_id = 1234;
+        order.oca_type = 3;
+        order.aux_price = Some(dec!(0));
+        assert_eq!(
+            TWSIncommingMessageImpl::OpenOrder {
+                order_id: 7,
+                contract: OrderContract {
+                    con_id: 265598,
+                    symbol: "AAPL",
+                    sec_type: "STK",
+                    last_trade_date_or_contract_month: "",
+                    strike: dec!(0),
+                    right: "?",
+                    multiplier: "",
+                    exchange: "SMART",
+                    currency: "USD",
+                    local_symbol: "AAPL",
+                    trading_class: "NMS",
+                },
+                order,
+                order_state: OrderState {
+                    status: InnerOrderStatus::PreSubmitted,
+                    init_margin_before: None,
+                    maint_margin_before: None,
+                    equity_with_loan_before: None,
+                    init_margin_change: None,
+                    maint_margin_change: None,
+                    equity_with_loan_change: None,
+                    init_margin_after: None,
+                    maint_margin_after: None,
+                    equity_with_loan_after: None,
+                    commission: None,
+                    min_commission: None,
+                    max_commission: None,
+                    commission_currency: "",
+                    warning_text: "",
+                },
+            },
+            parsed
+        );
+        assert_eq!(Some(7), parsed.get_order_id());
+    }
+
+    #[test]
+    fn can_ser_place_order() {
+        let contract = twsapi::core::contract::Contract {
+            symbol: "AAPL".to_owned(),
+            sec_type: "STK".to_owned(),
+            exchange: "SMART".to_owned(),
+            currency: "USD".to_owned(),
+            ..Default::default()
+        };
+        let mut order = Order::limit(Action::Sell, 10.0, dec!(150.5));
+        order.tif = TimeInForce::Gtc;
+        let msg = TWSOutgoingMessage::PlaceOrder {
+            order_id: 42,
+            contract,
+            order,
+            server_version: versions::MAX_SERVER_VERSION,
+        };
+
+        let mut buf = Vec::new();
+        ser::to_writer(&msg, &mut buf).unwrap();
+        let fields = buf.split(|b| *b == 0).collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                &b"3"[..],
+                b"42",
+                b"0",
+                b"AAPL",
+                b"STK",
+                b"",
+                b"0.0",
+                b"",
+                b"",
+                b"SMART",
+                b"",
+                b"USD",
+                b"",
+                b"",
+                b"",
+                b"",
+                b"SELL",
+                b"10.0",
+                b"LMT",
+                b"150.5",
+                b"",
+                b"GTC",
+            ],
+            fields[..22]
+        );
+        //unset doubles are sent empty like IB's sendMax does
+        assert_eq!(
+            vec![
+                &b""[..],
+                b"",
+                b"",
+                b"",
+                b"",
+                b"0",
+                b"",
+                b"",
+                b"",
+                b"",
+                b"",
+                b"",
+                b"",
+                b"",
+                b"0",
+                b"0",
+                b"0",
+                b"",
+            ],
+            fields[fields.len() - 19..fields.len() - 1]
+        );
+        //the trailing \0 leaves an empty last element
+        assert_eq!(Some(&&b""[..]), fields.last());
+    }
     //
 
     #[test]
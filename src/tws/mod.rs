@@ -1,5 +1,17 @@
+pub mod account;
+pub mod bar_series;
 pub mod client;
 pub mod codec;
 pub mod easy_client;
+pub mod error;
+pub mod executions;
+pub mod historical;
 pub mod messages;
+pub mod order_book;
+pub mod orders;
+pub mod pacing;
+pub mod positions;
+pub mod rate_limit;
+pub mod recording;
 pub mod serde;
+pub mod versions;
@@ -0,0 +1,185 @@
+use super::{
+    client::{Response, Subscription},
+    error::Error,
+    messages::{Currency, DepthOperation, DepthSide, TWSIncommingMessageImpl},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthLevel {
+    pub price: Currency,
+    pub size: i64,
+    //only set for L2 depth
+    pub market_maker: Option<String>,
+}
+
+/// Bids and asks by position, best first, as maintained from `MarketDepth`
+/// and `MarketDepthL2` messages.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OrderBook {
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+}
+
+impl OrderBook {
+    pub fn new() -> OrderBook {
+        OrderBook::default()
+    }
+
+    pub fn best_bid(&self) -> Option<&DepthLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&DepthLevel> {
+        self.asks.first()
+    }
+
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    /// Applies a single depth operation. Positions past the end of the book
+    /// are appended, TWS may skip rows it has no data for.
+    pub fn apply(
+        &mut self,
+        position: usize,
+        operation: DepthOperation,
+        side: DepthSide,
+        level: DepthLevel,
+    ) {
+        let levels = match side {
+            DepthSide::Bid => &mut self.bids,
+            DepthSide::Ask => &mut self.asks,
+        };
+        match operation {
+            DepthOperation::Insert => levels.insert(position.min(levels.len()), level),
+            DepthOperation::Update => match levels.get_mut(position) {
+                Some(l) => *l = level,
+                None => levels.push(level),
+            },
+            DepthOperation::Delete => {
+                if position < levels.len() {
+                    levels.remove(position);
+                }
+            }
+        }
+    }
+
+    /// Applies a depth message, returns whether `msg` was one.
+    pub fn apply_message(&mut self, msg: &TWSIncommingMessageImpl) -> bool {
+        match *msg {
+            TWSIncommingMessageImpl::MarketDepth {
+                position,
+                operation,
+                side,
+                price,
+                size,
+                ..
+            } => self.apply(
+                position as usize,
+                operation,
+                side,
+                DepthLevel {
+                    price,
+                    size,
+                    market_maker: None,
+                },
+            ),
+            TWSIncommingMessageImpl::MarketDepthL2 {
+                position,
+                market_maker,
+                operation,
+                side,
+                price,
+                size,
+                ..
+            } => self.apply(
+                position as usize,
+                operation,
+                side,
+                DepthLevel {
+                    price,
+                    size,
+                    market_maker: Some(market_maker.to_owned()),
+                },
+            ),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// A market depth subscription that yields the book after every update
+/// instead of the raw depth messages.
+#[derive(Debug)]
+pub struct OrderBookSubscription {
+    subscription: Subscription,
+    book: OrderBook,
+}
+
+impl OrderBookSubscription {
+    pub fn new(subscription: Subscription) -> OrderBookSubscription {
+        OrderBookSubscription {
+            subscription,
+            book: OrderBook::new(),
+        }
+    }
+
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+
+    /// Waits for the next depth update and returns the book with it applied.
+    /// The book is emptied on a disconnect, TWS rebuilds it once the request
+    /// is replayed.
+    pub async fn next(&mut self) -> Option<Result<&OrderBook, Error>> {
+        loop {
+            match self.subscription.recv().await? {
+                Ok(Response::Message(msg)) => {
+                    if self.book.apply_message(msg.get_msg()) {
+                        break;
+                    }
+                }
+                Ok(Response::Disconnected) => self.book.clear(),
+                Ok(Response::Reconnected) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(Ok(&self.book))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DepthLevel, OrderBook};
+    use crate::tws::messages::{DepthOperation, DepthSide};
+    use fixed_macro::types::I44F20 as dec;
+
+    fn level(size: i64) -> DepthLevel {
+        DepthLevel {
+            price: dec!(10.5),
+            size,
+            market_maker: None,
+        }
+    }
+
+    #[test]
+    fn applies_operations_by_position_and_side() {
+        let mut book = OrderBook::new();
+        book.apply(0, DepthOperation::Insert, DepthSide::Bid, level(1));
+        book.apply(0, DepthOperation::Insert, DepthSide::Bid, level(2));
+        book.apply(1, DepthOperation::Update, DepthSide::Bid, level(3));
+        book.apply(0, DepthOperation::Insert, DepthSide::Ask, level(4));
+
+        assert_eq!(vec![level(2), level(3)], book.bids);
+        assert_eq!(vec![level(4)], book.asks);
+
+        book.apply(0, DepthOperation::Delete, DepthSide::Bid, level(0));
+        assert_eq!(Some(&level(3)), book.best_bid());
+
+        //out of range deletes are ignored, out of range updates append
+        book.apply(5, DepthOperation::Delete, DepthSide::Ask, level(0));
+        book.apply(5, DepthOperation::Update, DepthSide::Ask, level(5));
+        assert_eq!(vec![level(4), level(5)], book.asks);
+    }
+}
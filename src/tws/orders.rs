@@ -0,0 +1,50 @@
+use super::{
+    messages::{InnerOrderStatus, Order, TWSIncommingMessageImpl},
+    positions::PositionContract,
+};
+
+/// An order as reported by `OpenOrder` or `CompletedOrder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderSnapshot {
+    //0 for completed orders and orders placed from TWS
+    pub order_id: i32,
+    pub contract: PositionContract,
+    pub order: Order,
+    pub status: InnerOrderStatus,
+    //only set for completed orders
+    pub completed_time: Option<String>,
+    pub completed_status: Option<String>,
+}
+
+impl OrderSnapshot {
+    pub fn from_message(msg: &TWSIncommingMessageImpl) -> Option<OrderSnapshot> {
+        match msg {
+            TWSIncommingMessageImpl::OpenOrder {
+                order_id,
+                contract,
+                order,
+                order_state,
+            } => Some(OrderSnapshot {
+                order_id: *order_id,
+                contract: contract.into(),
+                order: order.clone(),
+                status: order_state.status,
+                completed_time: None,
+                completed_status: None,
+            }),
+            TWSIncommingMessageImpl::CompletedOrder {
+                contract,
+                order,
+                order_state,
+            } => Some(OrderSnapshot {
+                order_id: 0,
+                contract: contract.into(),
+                order: order.clone(),
+                status: order_state.status,
+                completed_time: Some(order_state.completed_time.to_owned()),
+                completed_status: Some(order_state.completed_status.to_owned()),
+            }),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+
+use tokio::time::{Duration, Instant};
+
+//IB answers with error 162 when any of these is exceeded
+const WINDOW: Duration = Duration::from_secs(10 * 60);
+const MAX_PER_WINDOW: usize = 60;
+const BURST_WINDOW: Duration = Duration::from_secs(2);
+//six or more for the same contract, exchange and data type is a violation
+const MAX_PER_BURST: usize = 5;
+const IDENTICAL_WINDOW: Duration = Duration::from_secs(15);
+
+/// Identifies a historical request for pacing. `contract` covers contract,
+/// exchange and data type, `request` every parameter but the req_id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacingKey {
+    pub contract: String,
+    pub request: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacingStatus {
+    /// Requests waiting to be sent.
+    pub queued: usize,
+    /// How long until the last of them is sent, if no others arrive.
+    pub estimated_wait: Duration,
+}
+
+/// Tracks the historical requests sent in the last 10 minutes and those
+/// waiting for their turn, and tells when the next one may be sent without
+/// breaking IB's pacing rules.
+#[derive(Debug, Clone, Default)]
+pub struct Pacer {
+    sent: VecDeque<(Instant, PacingKey)>,
+    queue: VecDeque<(u64, PacingKey)>,
+    next_ticket: u64,
+}
+
+impl Pacer {
+    pub fn new() -> Pacer {
+        Pacer::default()
+    }
+
+    /// The earliest time a request may be sent at, given everything sent so
+    /// far.
+    pub fn next_slot(&self, now: Instant, key: &PacingKey) -> Instant {
+        let mut at = now;
+        if self.sent.len() >= MAX_PER_WINDOW {
+            at = at.max(self.sent[self.sent.len() - MAX_PER_WINDOW].0 + WINDOW);
+        }
+        let same_contract = self
+            .sent
+            .iter()
+            .filter(|(_, k)| k.contract == key.contract)
+            .map(|(t, _)| *t)
+            .collect::<Vec<_>>();
+        if same_contract.len() >= MAX_PER_BURST {
+            at = at.max(same_contract[same_contract.len() - MAX_PER_BURST] + BURST_WINDOW);
+        }
+        if let Some((t, _)) = self
+            .sent
+            .iter()
+            .rev()
+            .find(|(_, k)| k.request == key.request)
+        {
+            at = at.max(*t + IDENTICAL_WINDOW);
+        }
+        at
+    }
+
+    /// Queues a request, returns the ticket to `release` or `withdraw` it by.
+    pub fn enqueue(&mut self, key: PacingKey) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.queue.push_back((ticket, key));
+        ticket
+    }
+
+    /// Whether a request for the same contract was queued before `ticket`,
+    /// those go first.
+    pub fn is_behind(&self, ticket: u64, key: &PacingKey) -> bool {
+        self.queue
+            .iter()
+            .take_while(|(t, _)| *t != ticket)
+            .any(|(_, k)| k.contract == key.contract)
+    }
+
+    /// Takes a queued request off the queue and records it as sent at `at`.
+    pub fn release(&mut self, ticket: u64, at: Instant) {
+        if let Some(key) = self.take(ticket) {
+            self.record(at, key);
+        }
+    }
+
+    /// Takes a queued request off the queue without sending it.
+    pub fn withdraw(&mut self, ticket: u64) {
+        self.take(ticket);
+    }
+
+    pub fn record(&mut self, at: Instant, key: PacingKey) {
+        self.sent.push_back((at, key));
+        while self.sent.front().is_some_and(|(t, _)| *t + WINDOW <= at) {
+            self.sent.pop_front();
+        }
+    }
+
+    pub fn status(&self, now: Instant) -> PacingStatus {
+        let mut sim = Pacer {
+            sent: self.sent.clone(),
+            ..Pacer::default()
+        };
+        let mut at = now;
+        for (_, key) in self.queue.iter() {
+            at = sim.next_slot(at, key);
+            sim.record(at, key.clone());
+        }
+        PacingStatus {
+            queued: self.queue.len(),
+            estimated_wait: at - now,
+        }
+    }
+
+    fn take(&mut self, ticket: u64) -> Option<PacingKey> {
+        let i = self.queue.iter().position(|(t, _)| *t == ticket)?;
+        self.queue.remove(i).map(|(_, key)| key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pacer, PacingKey};
+    use tokio::time::{Duration, Instant};
+
+    fn key(contract: &str, request: &str) -> PacingKey {
+        PacingKey {
+            contract: contract.to_owned(),
+            request: request.to_owned(),
+        }
+    }
+
+    #[test]
+    fn spaces_identical_and_same_contract_requests() {
+        let now = Instant::now();
+        let mut pacer = Pacer::new();
+        pacer.record(now, key("AAPL", "1 D"));
+        assert_eq!(
+            now + Duration::from_secs(15),
+            pacer.next_slot(now, &key("AAPL", "1 D"))
+        );
+
+        for i in 2..=5 {
+            let k = key("AAPL", &format!("{} D", i));
+            assert_eq!(now, pacer.next_slot(now, &k));
+            pacer.record(now, k);
+        }
+        assert_eq!(
+            now + Duration::from_secs(2),
+            pacer.next_slot(now, &key("AAPL", "6 D"))
+        );
+        assert_eq!(now, pacer.next_slot(now, &key("MSFT", "MSFT 1 D")));
+    }
+
+    #[test]
+    fn allows_sixty_requests_per_ten_minutes() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new();
+        for i in 0..60 {
+            pacer.record(
+                start + Duration::from_secs(i * 3),
+                key(&i.to_string(), &i.to_string()),
+            );
+        }
+        let now = start + Duration::from_secs(180);
+        assert_eq!(
+            start + Duration::from_secs(600),
+            pacer.next_slot(now, &key("new", "new"))
+        );
+
+        let first = pacer.enqueue(key("a", "a"));
+        pacer.enqueue(key("b", "b"));
+        let status = pacer.status(now);
+        assert_eq!(2, status.queued);
+        assert_eq!(Duration::from_secs(423), status.estimated_wait);
+
+        pacer.withdraw(first);
+        assert_eq!(Duration::from_secs(420), pacer.status(now).estimated_wait);
+    }
+
+    #[test]
+    fn queues_behind_the_same_contract_only() {
+        let mut pacer = Pacer::new();
+        let first = pacer.enqueue(key("AAPL", "1 D"));
+        let msft = pacer.enqueue(key("MSFT", "1 D"));
+        let second = pacer.enqueue(key("AAPL", "2 D"));
+        assert!(!pacer.is_behind(first, &key("AAPL", "1 D")));
+        assert!(!pacer.is_behind(msft, &key("MSFT", "1 D")));
+        assert!(pacer.is_behind(second, &key("AAPL", "2 D")));
+
+        pacer.release(first, Instant::now());
+        assert!(!pacer.is_behind(second, &key("AAPL", "2 D")));
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+
+use super::{
+    client::{Response, Subscription},
+    error::Error,
+    messages::{Currency, OrderContract, TWSIncommingMessageImpl},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PositionKey {
+    pub account: String,
+    //empty for positions from `req_positions`
+    pub model_code: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionContract {
+    pub con_id: i32,
+    pub symbol: String,
+    pub sec_type: String,
+    pub last_trade_date_or_contract_month: String,
+    pub strike: Currency,
+    pub right: String,
+    pub multiplier: String,
+    pub exchange: String,
+    pub currency: String,
+    pub local_symbol: String,
+    pub trading_class: String,
+}
+
+impl From<&OrderContract<'_>> for PositionContract {
+    fn from(c: &OrderContract) -> Self {
+        PositionContract {
+            con_id: c.con_id,
+            symbol: c.symbol.to_owned(),
+            sec_type: c.sec_type.to_owned(),
+            last_trade_date_or_contract_month: c.last_trade_date_or_contract_month.to_owned(),
+            strike: c.strike,
+            right: c.right.to_owned(),
+            multiplier: c.multiplier.to_owned(),
+            exchange: c.exchange.to_owned(),
+            currency: c.currency.to_owned(),
+            local_symbol: c.local_symbol.to_owned(),
+            trading_class: c.trading_class.to_owned(),
+        }
+    }
+}
+
+/// A position as reported by `Position`/`PositionMulti`. The valuation
+/// fields are only known once a `PortfolioValue` for it arrived, which
+/// requires account updates for the account, see `req_position_book`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionEntry {
+    pub contract: PositionContract,
+    pub position: f64,
+    pub average_cost: f64,
+    pub market_price: Option<f64>,
+    pub market_value: Option<f64>,
+    pub unrealized_pnl: Option<f64>,
+    pub realized_pnl: Option<f64>,
+}
+
+/// A position that changed, `before`/`after` are `None` when it was opened
+/// or closed respectively.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionChange {
+    pub key: PositionKey,
+    pub con_id: i32,
+    pub before: Option<PositionEntry>,
+    pub after: Option<PositionEntry>,
+}
+
+type Positions = HashMap<PositionKey, HashMap<i32, PositionEntry>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Valuation {
+    market_price: f64,
+    market_value: f64,
+    unrealized_pnl: f64,
+    realized_pnl: f64,
+}
+
+impl Valuation {
+    fn apply(&self, entry: &mut PositionEntry) {
+        entry.market_price = Some(self.market_price);
+        entry.market_value = Some(self.market_value);
+        entry.unrealized_pnl = Some(self.unrealized_pnl);
+        entry.realized_pnl = Some(self.realized_pnl);
+    }
+}
+
+/// Live positions per account and model code. TWS first sends every position
+/// followed by an end marker, after which it sends single updates. The
+/// positions received before the end marker are collected separately and
+/// swapped in as a whole, so the book never shows a partial refresh.
+#[derive(Debug, Clone)]
+pub struct PositionBook {
+    positions: Positions,
+    //positions of the refresh in progress
+    pending: Option<Positions>,
+    //latest `PortfolioValue` per account and con_id, it may arrive before
+    //the position it values
+    valuations: HashMap<(String, i32), Valuation>,
+}
+
+impl Default for PositionBook {
+    fn default() -> Self {
+        PositionBook {
+            positions: Positions::default(),
+            pending: Some(Positions::default()),
+            valuations: HashMap::default(),
+        }
+    }
+}
+
+impl PositionBook {
+    pub fn new() -> PositionBook {
+        PositionBook::default()
+    }
+
+    /// Whether a full refresh completed and no new one is in progress.
+    pub fn is_current(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    pub fn get(&self, account: &str, model_code: &str, con_id: i32) -> Option<&PositionEntry> {
+        self.positions
+            .get(&PositionKey {
+                account: account.to_owned(),
+                model_code: model_code.to_owned(),
+            })
+            .and_then(|p| p.get(&con_id))
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = (&PositionKey, &PositionEntry)> {
+        self.positions
+            .iter()
+            .flat_map(|(k, p)| p.values().map(move |e| (k, e)))
+    }
+
+    /// Starts a new refresh, e.g. after a reconnect replayed the request.
+    pub fn invalidate(&mut self) {
+        self.pending = Some(Positions::default());
+    }
+
+    /// Applies a position or portfolio message and returns the changes it
+    /// caused. Positions received during a refresh are only reported by the
+    /// message that completes it.
+    pub fn apply_message(&mut self, msg: &TWSIncommingMessageImpl) -> Vec<PositionChange> {
+        match *msg {
+            TWSIncommingMessageImpl::Position {
+                account,
+                ref contract,
+                position,
+                average_cost,
+                ..
+            } => self.update(account, "", contract, position, average_cost),
+            TWSIncommingMessageImpl::PositionMulti {
+                account,
+                ref contract,
+                position,
+                average_cost,
+                model_code,
+                ..
+            } => self.update(account, model_code, contract, position, average_cost),
+            TWSIncommingMessageImpl::PortfolioValue {
+                ref contract,
+                market_price,
+                market_value,
+                unrealized_pnl,
+                realized_pnl,
+                account_name,
+                ..
+            } => {
+                let valuation = Valuation {
+                    market_price,
+                    market_value,
+                    unrealized_pnl,
+                    realized_pnl,
+                };
+                self.value(account_name, contract.con_id, valuation)
+            }
+            TWSIncommingMessageImpl::PositionEnd { .. }
+            | TWSIncommingMessageImpl::PositionMultiEnd { .. } => self.complete_refresh(),
+            _ => vec![],
+        }
+    }
+
+    fn update(
+        &mut self,
+        account: &str,
+        model_code: &str,
+        contract: &OrderContract,
+        position: f64,
+        average_cost: f64,
+    ) -> Vec<PositionChange> {
+        let key = PositionKey {
+            account: account.to_owned(),
+            model_code: model_code.to_owned(),
+        };
+        let refreshing = self.pending.is_some();
+        //account updates only value positions without a model code
+        let valuation = match model_code {
+            "" => self
+                .valuations
+                .get(&(account.to_owned(), contract.con_id))
+                .copied(),
+            _ => None,
+        };
+        let positions = self
+            .pending
+            .as_mut()
+            .unwrap_or(&mut self.positions)
+            .entry(key.clone())
+            .or_default();
+
+        let before = if position == 0.0 {
+            positions.remove(&contract.con_id)
+        } else {
+            let mut entry = PositionEntry {
+                contract: contract.into(),
+                position,
+                average_cost,
+                market_price: None,
+                market_value: None,
+                unrealized_pnl: None,
+                realized_pnl: None,
+            };
+            if let Some(v) = valuation {
+                v.apply(&mut entry);
+            }
+            positions.insert(contract.con_id, entry)
+        };
+
+        if refreshing {
+            return vec![];
+        }
+        let after = positions.get(&contract.con_id).cloned();
+        if before == after {
+            return vec![];
+        }
+        vec![PositionChange {
+            key,
+            con_id: contract.con_id,
+            before,
+            after,
+        }]
+    }
+
+    fn value(&mut self, account: &str, con_id: i32, valuation: Valuation) -> Vec<PositionChange> {
+        self.valuations
+            .insert((account.to_owned(), con_id), valuation);
+        let key = PositionKey {
+            account: account.to_owned(),
+            model_code: String::new(),
+        };
+        let refreshing = self.pending.is_some();
+        let entry = self
+            .pending
+            .as_mut()
+            .unwrap_or(&mut self.positions)
+            .get_mut(&key)
+            .and_then(|p| p.get_mut(&con_id));
+        let entry = match entry {
+            Some(e) => e,
+            None => return vec![],
+        };
+        let before = entry.clone();
+        valuation.apply(entry);
+        if refreshing || before == *entry {
+            return vec![];
+        }
+        vec![PositionChange {
+            key,
+            con_id,
+            after: Some(entry.clone()),
+            before: Some(before),
+        }]
+    }
+
+    fn complete_refresh(&mut self) -> Vec<PositionChange> {
+        let mut fresh = match self.pending.take() {
+            Some(fresh) => fresh,
+            None => return vec![],
+        };
+        fresh.retain(|_, p| !p.is_empty());
+        let old = std::mem::replace(&mut self.positions, fresh);
+
+        let mut changes = vec![];
+        for (key, positions) in self.positions.iter() {
+            for (con_id, after) in positions.iter() {
+                let before = old.get(key).and_then(|p| p.get(con_id));
+                if before != Some(after) {
+                    changes.push(PositionChange {
+                        key: key.clone(),
+                        con_id: *con_id,
+                        before: before.cloned(),
+                        after: Some(after.clone()),
+                    });
+                }
+            }
+        }
+        for (key, positions) in old.into_iter() {
+            for (con_id, before) in positions.into_iter() {
+                let gone = !matches!(self.positions.get(&key), Some(p) if p.contains_key(&con_id));
+                if gone {
+                    changes.push(PositionChange {
+                        key: key.clone(),
+                        con_id,
+                        before: Some(before),
+                        after: None,
+                    });
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// A positions subscription that maintains a `PositionBook` and yields the
+/// changes to it, optionally valued by an account updates subscription.
+#[derive(Debug)]
+pub struct PositionBookSubscription {
+    subscription: Subscription,
+    account_updates: Option<Subscription>,
+    book: PositionBook,
+}
+
+impl PositionBookSubscription {
+    pub fn new(subscription: Subscription) -> PositionBookSubscription {
+        PositionBookSubscription {
+            subscription,
+            account_updates: None,
+            book: PositionBook::new(),
+        }
+    }
+
+    /// Like `new` but also fills in the valuation fields from the
+    /// `PortfolioValue` messages of `account_updates`.
+    pub fn with_account_updates(
+        subscription: Subscription,
+        account_updates: Subscription,
+    ) -> PositionBookSubscription {
+        PositionBookSubscription {
+            account_updates: Some(account_updates),
+            ..PositionBookSubscription::new(subscription)
+        }
+    }
+
+    pub fn book(&self) -> &PositionBook {
+        &self.book
+    }
+
+    /// Waits for the next non-empty set of changes. After a disconnect the
+    /// book is no longer current until the replayed request completed a
+    /// refresh.
+    pub async fn next(&mut self) -> Option<Result<Vec<PositionChange>, Error>> {
+        loop {
+            let (valuing, response) = match &mut self.account_updates {
+                Some(account_updates) => tokio::select! {
+                    r = self.subscription.recv() => (false, r),
+                    r = account_updates.recv() => (true, r),
+                },
+                None => (false, self.subscription.recv().await),
+            };
+            match response {
+                //positions go on without valuations
+                None if valuing => self.account_updates = None,
+                None => return None,
+                Some(Ok(Response::Message(msg))) => {
+                    let changes = self.book.apply_message(msg.get_msg());
+                    if !changes.is_empty() {
+                        return Some(Ok(changes));
+                    }
+                }
+                //both are told, the positions subscription starts the refresh
+                Some(Ok(Response::Disconnected)) if !valuing => self.book.invalidate(),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PositionBook;
+    use crate::tws::messages::{OrderContract, PortfolioContract, TWSIncommingMessageImpl};
+    use fixed_macro::types::I44F20 as dec;
+
+    fn contract(con_id: i32) -> OrderContract<'static> {
+        OrderContract {
+            con_id,
+            symbol: "AAPL",
+            sec_type: "STK",
+            last_trade_date_or_contract_month: "",
+            strike: dec!(0),
+            right: "",
+            multiplier: "",
+            exchange: "NASDAQ",
+            currency: "USD",
+            local_symbol: "AAPL",
+            trading_class: "NMS",
+        }
+    }
+
+    fn position(con_id: i32, position: f64) -> TWSIncommingMessageImpl<'static> {
+        TWSIncommingMessageImpl::Position {
+            msg_version: 3,
+            account: "DU123",
+            contract: contract(con_id),
+            position,
+            average_cost: 10.0,
+        }
+    }
+
+    fn portfolio_value(con_id: i32, market_value: f64) -> TWSIncommingMessageImpl<'static> {
+        TWSIncommingMessageImpl::PortfolioValue {
+            msg_version: 8,
+            contract: PortfolioContract {
+                con_id,
+                symbol: "AAPL",
+                sec_type: "STK",
+                last_trade_date_or_contract_month: "",
+                strike: dec!(0),
+                right: "",
+                multiplier: "",
+                primary_exchange: "NASDAQ",
+                currency: "USD",
+                local_symbol: "AAPL",
+                trading_class: "NMS",
+            },
+            position: 100.0,
+            market_price: market_value / 100.0,
+            market_value,
+            average_cost: 10.0,
+            unrealized_pnl: market_value - 1000.0,
+            realized_pnl: 0.0,
+            account_name: "DU123",
+        }
+    }
+
+    const END: TWSIncommingMessageImpl = TWSIncommingMessageImpl::PositionEnd { msg_version: 1 };
+
+    #[test]
+    fn publishes_refresh_on_end() {
+        let mut book = PositionBook::new();
+        assert!(book.apply_message(&position(1, 100.0)).is_empty());
+        assert!(book.apply_message(&position(2, 5.0)).is_empty());
+        assert!(!book.is_current());
+        assert!(book.get("DU123", "", 1).is_none());
+
+        let changes = book.apply_message(&END);
+        assert_eq!(2, changes.len());
+        assert!(book.is_current());
+        assert_eq!(100.0, book.get("DU123", "", 1).unwrap().position);
+
+        let changes = book.apply_message(&position(1, 50.0));
+        assert_eq!(1, changes.len());
+        assert_eq!(Some(100.0), changes[0].before.as_ref().map(|e| e.position));
+        assert_eq!(Some(50.0), changes[0].after.as_ref().map(|e| e.position));
+    }
+
+    #[test]
+    fn refresh_reports_closed_positions() {
+        let mut book = PositionBook::new();
+        book.apply_message(&position(1, 100.0));
+        book.apply_message(&position(2, 5.0));
+        book.apply_message(&END);
+
+        book.invalidate();
+        book.apply_message(&position(1, 100.0));
+        let changes = book.apply_message(&END);
+
+        assert_eq!(1, changes.len());
+        assert_eq!(2, changes[0].con_id);
+        assert!(changes[0].after.is_none());
+        assert!(book.get("DU123", "", 2).is_none());
+    }
+
+    #[test]
+    fn values_positions_from_portfolio_updates() {
+        let mut book = PositionBook::new();
+        //account updates may answer before the positions do
+        assert!(book.apply_message(&portfolio_value(1, 1350.0)).is_empty());
+        book.apply_message(&position(1, 100.0));
+        book.apply_message(&END);
+        assert_eq!(Some(1350.0), book.get("DU123", "", 1).unwrap().market_value);
+
+        let changes = book.apply_message(&portfolio_value(1, 1400.0));
+        assert_eq!(1, changes.len());
+        assert_eq!(
+            Some(1350.0),
+            changes[0].before.as_ref().unwrap().market_value
+        );
+        assert_eq!(
+            Some(400.0),
+            changes[0].after.as_ref().unwrap().unrealized_pnl
+        );
+        assert!(book.apply_message(&portfolio_value(1, 1400.0)).is_empty());
+    }
+}
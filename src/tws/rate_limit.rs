@@ -0,0 +1,146 @@
+use tokio::time::{Duration, Instant};
+
+use super::error::Error;
+
+/// Limits the messages sent to TWS, which disconnects clients sending more
+/// than 50 per second. The default stays below that even after a burst.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            per_second: 40.0,
+            burst: 10,
+        }
+    }
+}
+
+impl RateLimit {
+    /// Checks that the limit lets messages through at all, `per_second` must
+    /// be positive and finite.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.per_second.is_finite() && self.per_second > 0.0 {
+            Ok(())
+        } else {
+            Err(Error::InvalidRateLimit {
+                per_second: self.per_second,
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ThrottleStats {
+    pub sent: u64,
+    /// Messages that had to wait for a token.
+    pub throttled: u64,
+    /// The time spent waiting in total.
+    pub throttled_time: Duration,
+}
+
+/// A token bucket that hands out reservations: a message without a token
+/// takes one ahead of time and waits until it would have been refilled.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last: Instant,
+    stats: ThrottleStats,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit, now: Instant) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: limit.burst as f64,
+            last: now,
+            stats: ThrottleStats::default(),
+        }
+    }
+
+    /// Takes a token, returns how long to wait before sending.
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.last = now;
+        self.tokens -= 1.0;
+        self.stats.sent += 1;
+
+        if self.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        let wait = Duration::from_secs_f64(-self.tokens / self.limit.per_second);
+        self.stats.throttled += 1;
+        self.stats.throttled_time += wait;
+        wait
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimit, TokenBucket};
+    use crate::Error;
+    use tokio::time::{Duration, Instant};
+
+    #[test]
+    fn rejects_limits_without_throughput() {
+        for per_second in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let limit = RateLimit {
+                per_second,
+                burst: 10,
+            };
+            assert!(matches!(
+                limit.validate(),
+                Err(Error::InvalidRateLimit { .. })
+            ));
+        }
+        assert!(RateLimit::default().validate().is_ok());
+    }
+
+    #[test]
+    fn bursts_then_spaces_messages() {
+        let now = Instant::now();
+        let limit = RateLimit {
+            per_second: 10.0,
+            burst: 2,
+        };
+        let mut bucket = TokenBucket::new(limit, now);
+
+        assert_eq!(Duration::ZERO, bucket.reserve(now));
+        assert_eq!(Duration::ZERO, bucket.reserve(now));
+        assert_eq!(Duration::from_millis(100), bucket.reserve(now));
+        assert_eq!(Duration::from_millis(200), bucket.reserve(now));
+
+        //after waiting its turn the next one is due another 100ms later
+        let later = now + Duration::from_millis(200);
+        assert_eq!(Duration::from_millis(100), bucket.reserve(later));
+
+        let stats = bucket.stats();
+        assert_eq!(5, stats.sent);
+        assert_eq!(3, stats.throttled);
+        assert_eq!(Duration::from_millis(400), stats.throttled_time);
+    }
+
+    #[test]
+    fn refills_up_to_burst() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::default(), now);
+        for _ in 0..10 {
+            assert_eq!(Duration::ZERO, bucket.reserve(now));
+        }
+
+        let later = now + Duration::from_secs(60);
+        for _ in 0..10 {
+            assert_eq!(Duration::ZERO, bucket.reserve(later));
+        }
+        assert!(bucket.reserve(later) > Duration::ZERO);
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::mpsc as std_mpsc,
+    thread,
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::warn;
+
+use super::{client::route_id, codec::TWSFrame, messages::TWSIncommingMessage};
+
+// A recording is a sequence of frames, each written as
+//
+//    direction: u8, time: i64 microseconds since the epoch, length: u32,
+//    payload: the frame as sent on the wire without its length prefix
+//
+// with all integers big endian like the wire protocol.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// TWS's answer to the version handshake, its server version and time.
+    Handshake = 0,
+    Inbound = 1,
+    Outbound = 2,
+}
+
+impl Direction {
+    fn from_u8(b: u8) -> Option<Direction> {
+        match b {
+            0 => Some(Direction::Handshake),
+            1 => Some(Direction::Inbound),
+            2 => Some(Direction::Outbound),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedFrame {
+    pub at: DateTime<Utc>,
+    pub direction: Direction,
+    pub payload: Bytes,
+}
+
+impl RecordedFrame {
+    pub fn fields(&self) -> TWSFrame {
+        TWSFrame::new(self.payload.clone())
+    }
+}
+
+enum Command {
+    Frame(DateTime<Utc>, Direction, Vec<u8>),
+    Flush(std_mpsc::Sender<io::Result<()>>),
+}
+
+/// Writes the frames passing through a `TWSCodec` to a file, see
+/// `TWSCodec::with_recorder`. Frames are handed to a writer thread so the
+/// codec never blocks on the file. Clones write to the same file, which is
+/// buffered and flushed when the last clone is dropped or on `flush`.
+#[derive(Clone)]
+pub struct Recorder {
+    frames: std_mpsc::Sender<Command>,
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder").finish()
+    }
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Recorder> {
+        Ok(Recorder::new(BufWriter::new(File::create(path)?)))
+    }
+
+    pub fn new<W: Write + Send + 'static>(mut out: W) -> Recorder {
+        let (frames, commands) = std_mpsc::channel();
+        thread::spawn(move || {
+            for command in commands {
+                match command {
+                    Command::Frame(at, direction, payload) => {
+                        //failures are logged, they shouldn't take the session down
+                        if let Err(e) = write(&mut out, at, direction, &payload) {
+                            warn!(?e, "Failed to record frame");
+                        }
+                    }
+                    Command::Flush(done) => {
+                        let _ = done.send(out.flush());
+                    }
+                }
+            }
+            if let Err(e) = out.flush() {
+                warn!(?e, "Failed to flush recording");
+            }
+        });
+        Recorder { frames }
+    }
+
+    /// Queues a frame stamped with the current time for writing.
+    pub fn record(&self, direction: Direction, payload: &[u8]) {
+        self.record_at(Utc::now(), direction, payload);
+    }
+
+    /// Blocks until the frames recorded so far are written and flushed.
+    pub fn flush(&self) -> io::Result<()> {
+        let (done, result) = std_mpsc::channel();
+        let stopped = || io::Error::new(io::ErrorKind::BrokenPipe, "recorder stopped");
+        self.frames
+            .send(Command::Flush(done))
+            .map_err(|_| stopped())?;
+        result.recv().map_err(|_| stopped())?
+    }
+
+    fn record_at(&self, at: DateTime<Utc>, direction: Direction, payload: &[u8]) {
+        let _ = self
+            .frames
+            .send(Command::Frame(at, direction, payload.to_vec()));
+    }
+}
+
+fn write<W: Write>(
+    out: &mut W,
+    at: DateTime<Utc>,
+    direction: Direction,
+    payload: &[u8],
+) -> io::Result<()> {
+    out.write_all(&[direction as u8])?;
+    out.write_all(&at.timestamp_micros().to_be_bytes())?;
+    out.write_all(&(payload.len() as u32).to_be_bytes())?;
+    out.write_all(payload)
+}
+
+/// Reads all frames of a recording.
+pub fn read_frames<R: Read>(mut input: R) -> io::Result<Vec<RecordedFrame>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    let mut frames = vec![];
+    let mut header = [0u8; 13];
+    loop {
+        match input.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(frames),
+            Err(e) => return Err(e),
+        }
+        let direction = Direction::from_u8(header[0]).ok_or_else(|| invalid("bad direction"))?;
+        let micros = i64::from_be_bytes(header[1..9].try_into().unwrap());
+        let at = Utc
+            .timestamp_opt(
+                micros.div_euclid(1_000_000),
+                (micros.rem_euclid(1_000_000) * 1000) as u32,
+            )
+            .single()
+            .ok_or_else(|| invalid("bad timestamp"))?;
+        let len = u32::from_be_bytes(header[9..13].try_into().unwrap()) as usize;
+        let mut payload = vec![0u8; len];
+        input.read_exact(&mut payload)?;
+        frames.push(RecordedFrame {
+            at,
+            direction,
+            payload: payload.into(),
+        });
+    }
+}
+
+/// Feeds the inbound frames of a recording back as messages, routed by
+/// order or req_id like `ClientImpl` does.
+pub struct Replayer {
+    frames: Vec<RecordedFrame>,
+    routes: HashMap<i32, UnboundedSender<TWSIncommingMessage>>,
+    unrouted: Option<UnboundedSender<TWSIncommingMessage>>,
+}
+
+impl Replayer {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Replayer> {
+        Ok(Replayer::new(read_frames(BufReader::new(File::open(
+            path,
+        )?))?))
+    }
+
+    pub fn new(frames: Vec<RecordedFrame>) -> Replayer {
+        Replayer {
+            frames,
+            routes: HashMap::new(),
+            unrouted: None,
+        }
+    }
+
+    /// The messages for an order or req_id, as recorded.
+    pub fn subscribe(&mut self, id: i32) -> UnboundedReceiver<TWSIncommingMessage> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.routes.insert(id, sender);
+        receiver
+    }
+
+    /// Every message no subscription took.
+    pub fn unrouted(&mut self) -> UnboundedReceiver<TWSIncommingMessage> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.unrouted = Some(sender);
+        receiver
+    }
+
+    /// Replays the recording `speed` times as fast as it was recorded,
+    /// `f64::INFINITY` for no delays at all. Returns the number of messages
+    /// replayed, frames that fail to decode are logged and skipped.
+    pub async fn run(self, speed: f64) -> usize {
+        let mut server_version = None;
+        let mut last: Option<DateTime<Utc>> = None;
+        let mut replayed = 0;
+        let Replayer {
+            frames,
+            routes,
+            unrouted,
+        } = self;
+        for frame in frames {
+            if let Some(last) = last {
+                let gap = (frame.at - last).to_std().unwrap_or_default();
+                if speed.is_finite() && !gap.is_zero() {
+                    tokio::time::sleep(gap.div_f64(speed)).await;
+                }
+            }
+            last = Some(frame.at);
+
+            match frame.direction {
+                Direction::Handshake => {
+                    let fields = frame.fields();
+                    server_version = fields
+                        .first()
+                        .and_then(|v| std::str::from_utf8(v).ok())
+                        .and_then(|v| v.parse().ok());
+                }
+                Direction::Inbound => {
+                    let decoded = match server_version {
+                        Some(v) => TWSIncommingMessage::from_decoded_message_with_version(
+                            frame.fields(),
+                            v,
+                        ),
+                        None => TWSIncommingMessage::from_decoded_message(frame.fields()),
+                    };
+                    let msg = match decoded {
+                        Ok(msg) => msg,
+                        Err(e) => {
+                            warn!(?e, payload = ?frame.payload, "Skipping undecodable frame");
+                            continue;
+                        }
+                    };
+                    let route = route_id(msg.get_msg()).and_then(|id| routes.get(&id));
+                    if let Some(sender) = route.or(unrouted.as_ref()) {
+                        let _ = sender.send(msg);
+                    }
+                    replayed += 1;
+                }
+                Direction::Outbound => {}
+            }
+        }
+        replayed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_frames, Direction, Recorder, Replayer};
+    use crate::tws::{codec::TWSCodec, messages::TWSIncommingMessageImpl};
+    use bytes::{Bytes, BytesMut};
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+    use tokio_util::codec::{Decoder, Encoder};
+
+    //a `Write` the test can look into after handing it to the recorder
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn wire(payload: &[u8]) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[tokio::test]
+    async fn records_and_replays_a_session() {
+        let buffer = Buffer::default();
+        let recorder = Recorder::new(buffer.clone());
+        let mut codec = TWSCodec::with_recorder(recorder.clone());
+
+        codec
+            .decode(&mut wire(b"126\x0020210309 22:54:30 CET\0"))
+            .unwrap();
+        codec
+            .encode(vec![Bytes::from_static(b"87\x007\0")], &mut BytesMut::new())
+            .unwrap();
+        codec.decode(&mut wire(b"88\x007\x001618812000\0")).unwrap();
+        codec.decode(&mut wire(b"9\x001\x0042\0")).unwrap();
+        recorder.flush().unwrap();
+
+        let frames = read_frames(&buffer.0.lock().unwrap()[..]).unwrap();
+        assert_eq!(
+            vec![
+                Direction::Handshake,
+                Direction::Outbound,
+                Direction::Inbound,
+                Direction::Inbound
+            ],
+            frames.iter().map(|f| f.direction).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![&b"87"[..], b"7"],
+            frames[1].fields().iter().collect::<Vec<_>>()
+        );
+
+        let mut replayer = Replayer::new(frames);
+        let mut head = replayer.subscribe(7);
+        let mut rest = replayer.unrouted();
+        assert_eq!(2, replayer.run(f64::INFINITY).await);
+
+        assert!(matches!(
+            head.recv().await.unwrap().get_msg(),
+            TWSIncommingMessageImpl::HeadTimestamp { req_id: 7, .. }
+        ));
+        assert!(matches!(
+            rest.recv().await.unwrap().get_msg(),
+            TWSIncommingMessageImpl::NextValidId { order_id: 42, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn skips_undecodable_frames() {
+        let buffer = Buffer::default();
+        let recorder = Recorder::new(buffer.clone());
+        let now = chrono::Utc::now();
+        recorder.record_at(now, Direction::Inbound, b"9\x001\x00nan\0");
+        recorder.record_at(now, Direction::Inbound, b"9\x001\x0042\0");
+        recorder.flush().unwrap();
+        let frames = read_frames(&buffer.0.lock().unwrap()[..]).unwrap();
+
+        let mut replayer = Replayer::new(frames);
+        let mut rest = replayer.unrouted();
+        assert_eq!(1, replayer.run(f64::INFINITY).await);
+        assert!(matches!(
+            rest.recv().await.unwrap().get_msg(),
+            TWSIncommingMessageImpl::NextValidId { order_id: 42, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn replays_at_recorded_pace() {
+        let buffer = Buffer::default();
+        let recorder = Recorder::new(buffer.clone());
+        let start = chrono::Utc::now();
+        recorder.record_at(start, Direction::Handshake, b"151\0");
+        for i in 0..3 {
+            recorder.record_at(
+                start + chrono::Duration::seconds(10 * i),
+                Direction::Inbound,
+                b"9\x001\x001\0",
+            );
+        }
+        recorder.flush().unwrap();
+        let frames = read_frames(&buffer.0.lock().unwrap()[..]).unwrap();
+
+        //20 seconds at 100 times the speed
+        let begin = std::time::Instant::now();
+        assert_eq!(3, Replayer::new(frames).run(100.0).await);
+        assert!(begin.elapsed() >= std::time::Duration::from_millis(200));
+    }
+}
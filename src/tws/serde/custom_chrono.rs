@@ -1,7 +1,9 @@
-use chrono::{DateTime, Local, TimeZone, Utc};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
 use serde::{self, Deserialize, Deserializer, Serializer};
 
 const FORMAT: &'static str = "%Y%m%d  %H:%M:%S";
+const DATE_FORMAT: &str = "%Y%m%d";
+const DATE_FORMAT_LEN: usize = 8;
 
 // The signature of a serialize_with function must follow the pattern:
 //
@@ -30,6 +32,14 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
+    //daily and longer bars only carry the date
+    if s.len() == DATE_FORMAT_LEN {
+        return NaiveDate::parse_from_str(&s, DATE_FORMAT)
+            .ok()
+            .and_then(|d| Local.from_local_datetime(&d.and_hms_opt(0, 0, 0)?).single())
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid date {}", s)));
+    }
     Local
         .datetime_from_str(&s, FORMAT)
         .map(|dt| dt.with_timezone(&Utc))
@@ -1,34 +1,90 @@
 use core::f64;
 
-use super::super::codec::DecodedMessage;
+use super::super::{
+    codec::{DecodedMessage, TWSFrame},
+    versions::MAX_SERVER_VERSION,
+};
 use super::error::{Error, Result};
 use bytes::Bytes;
-use serde::de::{self, IntoDeserializer, Visitor};
+use serde::de::{self, Deserialize, IntoDeserializer, Visitor};
 
 use simdutf8::basic::from_utf8;
 
 use std;
 use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+
+//newtype name `since` asks for the server version by
+const SERVER_VERSION: &str = "$tws::ServerVersion";
+
+/// The fields of a message, read by index.
+pub trait Fields: fmt::Debug {
+    fn field_count(&self) -> usize;
+    fn field(&self, i: usize) -> Option<&[u8]>;
+    /// Like `field` but sharing the message's buffer.
+    fn field_bytes(&self, i: usize) -> Option<Bytes>;
+}
 
-pub struct Deserializer<'de> {
-    // This string starts with the input data and characters are truncated off
-    // the beginning as data is parsed.
-    input: &'de DecodedMessage,
+impl Fields for DecodedMessage {
+    fn field_count(&self) -> usize {
+        self.len()
+    }
+    fn field(&self, i: usize) -> Option<&[u8]> {
+        self.get(i).map(|b| &b[..])
+    }
+    fn field_bytes(&self, i: usize) -> Option<Bytes> {
+        self.get(i).cloned()
+    }
+}
+
+impl Fields for TWSFrame {
+    fn field_count(&self) -> usize {
+        self.len()
+    }
+    fn field(&self, i: usize) -> Option<&[u8]> {
+        self.get(i)
+    }
+    fn field_bytes(&self, i: usize) -> Option<Bytes> {
+        self.get_bytes(i)
+    }
+}
+
+pub struct Deserializer<'de, F: Fields + ?Sized = TWSFrame> {
+    // The message's fields, `index` is the next one to be parsed.
+    input: &'de F,
     index: usize,
+    server_version: i32,
 }
 
-impl<'de> Deserializer<'de> {
+impl<'de, F: Fields + ?Sized> Deserializer<'de, F> {
     // By convention, `Deserializer` constructors are named like `from_xyz`.
     // That way basic use cases are satisfied by something like
     // `serde_json::from_str(...)` while advanced use cases that require a
     // deserializer can make one with `serde_json::Deserializer::from_str(...)`.
-    pub fn from_msg(input: &'de DecodedMessage) -> Self {
-        Deserializer { input, index: 0 }
+    pub fn from_msg(input: &'de F) -> Self {
+        Deserializer::from_msg_with_version(input, MAX_SERVER_VERSION)
     }
+
+    /// Like `from_msg` for a message sent by a server of `server_version`.
+    pub fn from_msg_with_version(input: &'de F, server_version: i32) -> Self {
+        Deserializer {
+            input,
+            index: 0,
+            server_version,
+        }
+    }
+
+    pub fn server_version(&self) -> i32 {
+        self.server_version
+    }
+    // Empty numbers, which TWS sends for unset values, parse as 0 and are
+    // consumed like any other field so the next one lines up.
     fn parse_f64(&mut self) -> Result<f64> {
         match self.peek_utf8_str() {
             Ok(s) => {
                 if s.is_empty() {
+                    self.advance();
                     Ok(0.0)
                 } else {
                     s.parse::<f64>()
@@ -49,6 +105,7 @@ impl<'de> Deserializer<'de> {
         match self.peek_utf8_str() {
             Ok(s) => {
                 if s.is_empty() {
+                    self.advance();
                     Ok(0.0)
                 } else {
                     s.parse::<f32>()
@@ -70,6 +127,7 @@ impl<'de> Deserializer<'de> {
         match self.peek_utf8_str() {
             Ok(s) => {
                 if s.is_empty() {
+                    self.advance();
                     Ok(0)
                 } else {
                     s.parse::<i32>()
@@ -91,6 +149,7 @@ impl<'de> Deserializer<'de> {
         match self.peek_utf8_str() {
             Ok(s) => {
                 if s.is_empty() {
+                    self.advance();
                     Ok(0)
                 } else {
                     s.parse::<i64>()
@@ -108,19 +167,14 @@ impl<'de> Deserializer<'de> {
         }
     }
 
+    // TWS sends booleans as 0 or 1. `parse_i32` already consumes the field.
     fn parse_bool(&mut self) -> Result<bool> {
-        match self.parse_i32() {
-            Ok(i) => {
-                self.advance();
-                Ok(i == 0)
-            }
-            Err(e) => Err(e),
-        }
+        self.parse_i32().map(|i| i != 0)
     }
 
     fn peek_utf8_str(&mut self) -> Result<&'de str> {
-        match self.input.get(self.index) {
-            Some(bytes) => from_utf8(&bytes[..]).map_err(|e| {
+        match self.input.field(self.index) {
+            Some(bytes) => from_utf8(bytes).map_err(|e| {
                 println!("Failed to parse utf8");
                 serde::de::Error::custom(format!("{:?}", e))
             }),
@@ -139,16 +193,15 @@ impl<'de> Deserializer<'de> {
     }
 
     fn current_is_empty(&self) -> Result<bool> {
-        match self.input.get(self.index) {
+        match self.input.field(self.index) {
             Some(bytes) => Ok(bytes.is_empty()),
             None => Ok(true),
         }
     }
 
     fn take_bytes(&mut self) -> Result<Bytes> {
-        match self.input.get(self.index) {
-            Some(bytes) => {
-                let b = bytes.clone();
+        match self.input.field_bytes(self.index) {
+            Some(b) => {
                 self.advance();
                 Ok(b)
             }
@@ -161,10 +214,10 @@ impl<'de> Deserializer<'de> {
     }
 }
 
-impl<'de> Drop for Deserializer<'de> {
+impl<'de, F: Fields + ?Sized> Drop for Deserializer<'de, F> {
     fn drop(&mut self) {
         //debug_assert_eq!(self.index, self.input.len())
-        if self.index + 1 < self.input.len() {
+        if self.index + 1 < self.input.field_count() {
             println!(
                 "Failed to fully deserialize >>{:?}<< only consumed {:?} elements",
                 self.input, self.index
@@ -173,7 +226,7 @@ impl<'de> Drop for Deserializer<'de> {
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de, F: Fields + ?Sized> de::Deserializer<'de> for &mut Deserializer<'de, F> {
     type Error = Error;
     // Look at the input data to decide what Serde data model type to
     // deserialize as. Not all data formats are able to support this operation.
@@ -334,7 +387,9 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        //an empty field is None, it's consumed so fields after it still line up
         if self.current_is_empty()? {
+            self.advance();
             visitor.visit_none()
         } else {
             visitor.visit_some(self)
@@ -361,10 +416,16 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     // As is done here, serializers are encouraged to treat newtype structs as
     // insignificant wrappers around the data they contain. That means not
     // parsing anything other than the contained value.
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == SERVER_VERSION {
+            return visitor.visit_seq(VersionedAccess {
+                server_version: Some(self.server_version),
+                deserializer: self,
+            });
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -377,7 +438,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     {
         let vec_size = usize::try_from(self.parse_i32()?).map_err(|_| Error::Syntax)?;
 
-        if vec_size > (self.input.len() - self.index) {
+        if vec_size > (self.input.field_count() - self.index) {
             return Err(Error::Eof);
         }
 
@@ -397,12 +458,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        struct Access<'a, 'de> {
-            deserializer: &'a mut Deserializer<'de>,
+        struct Access<'a, 'de, F: Fields + ?Sized> {
+            deserializer: &'a mut Deserializer<'de, F>,
             len: usize,
         }
 
-        impl<'a, 'de> serde::de::SeqAccess<'de> for Access<'a, 'de> {
+        impl<'a, 'de, F: Fields + ?Sized> serde::de::SeqAccess<'de> for Access<'a, 'de, F> {
             type Error = Error;
 
             fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -480,20 +541,6 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        impl<'a, 'de> serde::de::EnumAccess<'de> for &'a mut Deserializer<'de> {
-            type Error = Error;
-            type Variant = Self;
-
-            fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
-            where
-                V: serde::de::DeserializeSeed<'de>,
-            {
-                let idx = self.get_utf8_str()?;
-                let val: Result<_> = seed.deserialize(idx.into_deserializer());
-                Ok((val?, self))
-            }
-        }
-
         visitor.visit_enum(self)
     }
 
@@ -527,12 +574,100 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     }
 }
 
-struct Access<'a, 'de> {
-    deserializer: &'a mut Deserializer<'de>,
+struct Access<'a, 'de, F: Fields + ?Sized> {
+    deserializer: &'a mut Deserializer<'de, F>,
     len: usize,
 }
 
-impl<'a, 'de> serde::de::SeqAccess<'de> for Access<'a, 'de> {
+//yields the server version, then the field that follows in the message
+struct VersionedAccess<'a, 'de, F: Fields + ?Sized> {
+    deserializer: &'a mut Deserializer<'de, F>,
+    server_version: Option<i32>,
+}
+
+impl<'a, 'de, F: Fields + ?Sized> serde::de::SeqAccess<'de> for VersionedAccess<'a, 'de, F> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        match self.server_version.take() {
+            Some(v) => seed.deserialize(v.into_deserializer()).map(Some),
+            None => seed.deserialize(&mut *self.deserializer).map(Some),
+        }
+    }
+}
+
+/// A field the server only sends since `VERSION`, like `since` for
+/// hand-written visitors that read fields with `SeqAccess::next_element`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Since<const VERSION: i32, T>(pub T);
+
+impl<'de, const VERSION: i32, T> Deserialize<'de> for Since<VERSION, T>
+where
+    T: Deserialize<'de> + Default,
+{
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        since::<VERSION, D, T>(deserializer).map(Since)
+    }
+}
+
+/// A `deserialize_with` helper for fields the server only sends since
+/// `VERSION`, older servers leave them at their default. With formats other
+/// than ours the field is always read.
+///
+/// `#[serde(deserialize_with = "since::<{ versions::PRICE_MGMT_ALGO }, _, _>")]`
+pub fn since<'de, const VERSION: i32, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: de::Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    struct SinceVisitor<const VERSION: i32, T>(PhantomData<T>);
+
+    impl<'de, const VERSION: i32, T> Visitor<'de> for SinceVisitor<VERSION, T>
+    where
+        T: Deserialize<'de> + Default,
+    {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                formatter,
+                "a field present since server version {}",
+                VERSION
+            )
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<T, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            T::deserialize(deserializer)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> std::result::Result<T, A::Error>
+        where
+            A: de::SeqAccess<'de>,
+        {
+            let server_version: i32 = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            if server_version < VERSION {
+                return Ok(T::default());
+            }
+            seq.next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))
+        }
+    }
+
+    deserializer.deserialize_newtype_struct(SERVER_VERSION, SinceVisitor::<VERSION, T>(PhantomData))
+}
+
+impl<'a, 'de, F: Fields + ?Sized> serde::de::SeqAccess<'de> for Access<'a, 'de, F> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -553,7 +688,21 @@ impl<'a, 'de> serde::de::SeqAccess<'de> for Access<'a, 'de> {
     }
 }
 
-impl<'a, 'de> serde::de::VariantAccess<'de> for &'a mut Deserializer<'de> {
+impl<'de, F: Fields + ?Sized> serde::de::EnumAccess<'de> for &mut Deserializer<'de, F> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let idx = self.get_utf8_str()?;
+        let val: Result<_> = seed.deserialize(idx.into_deserializer());
+        Ok((val?, self))
+    }
+}
+
+impl<'de, F: Fields + ?Sized> serde::de::VariantAccess<'de> for &mut Deserializer<'de, F> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -584,7 +733,7 @@ impl<'a, 'de> serde::de::VariantAccess<'de> for &'a mut Deserializer<'de> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Deserializer, Error};
+    use super::{since, Deserializer, Error};
     use bytes::Bytes;
     use bytestring::ByteString;
     use serde::Deserialize;
@@ -741,6 +890,33 @@ mod tests {
         assert_eq!((1i32, None), Tup::deserialize(&mut de).unwrap());
     }
     #[test]
+    fn can_deser_option_none_in_the_middle() {
+        let msg = vec!["1".into(), "".into(), "2".into()];
+        let mut de = Deserializer::from_msg(&msg);
+
+        type Tup = (i32, Option<i32>, i32);
+
+        assert_eq!((1i32, None, 2i32), Tup::deserialize(&mut de).unwrap());
+    }
+    #[test]
+    fn can_deser_empty_number_as_zero() {
+        let msg = vec!["".into(), "2".into()];
+        let mut de = Deserializer::from_msg(&msg);
+
+        type Tup = (f64, i32);
+
+        assert_eq!((0.0, 2i32), Tup::deserialize(&mut de).unwrap());
+    }
+    #[test]
+    fn can_deser_bool() {
+        let msg = vec!["1".into(), "0".into(), "3".into()];
+        let mut de = Deserializer::from_msg(&msg);
+
+        type Tup = (bool, bool, i32);
+
+        assert_eq!((true, false, 3i32), Tup::deserialize(&mut de).unwrap());
+    }
+    #[test]
     fn can_deser_vec() {
         let msg = vec!["2".into(), "1".into(), "2".into()];
         let mut de = Deserializer::from_msg(&msg);
@@ -795,4 +971,38 @@ mod tests {
 
         assert_eq!(Err(Error::Eof), Tup::deserialize(&mut de));
     }
+    #[test]
+    fn skips_fields_newer_than_server_version() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Data<'a> {
+            int: i32,
+            #[serde(deserialize_with = "since::<120, _, _>")]
+            s: &'a str,
+            #[serde(deserialize_with = "since::<110, _, _>")]
+            other: i32,
+        }
+        let msg = vec!["1".into(), "foo".into(), "2".into()];
+
+        let mut de = Deserializer::from_msg_with_version(&msg, 120);
+        assert_eq!(
+            Data {
+                int: 1,
+                s: "foo",
+                other: 2
+            },
+            Data::deserialize(&mut de).unwrap()
+        );
+
+        //an older server doesn't send `s` at all
+        let msg = vec!["1".into(), "2".into()];
+        let mut de = Deserializer::from_msg_with_version(&msg, 110);
+        assert_eq!(
+            Data {
+                int: 1,
+                s: "",
+                other: 2
+            },
+            Data::deserialize(&mut de).unwrap()
+        );
+    }
 }
@@ -0,0 +1,11 @@
+//! Server versions the protocol changed at, named after IB's `MinServerVer`.
+//! Fields added to a message in one of them are read with `de::since`.
+
+/// The range of server versions negotiated during the handshake. Messages
+/// that changed below `MIN_SERVER_VERSION` are only decoded in their newer
+/// layout, completed orders need 150.
+pub const MIN_SERVER_VERSION: i32 = COMPLETED_ORDERS;
+pub const MAX_SERVER_VERSION: i32 = 151;
+
+pub const COMPLETED_ORDERS: i32 = 150;
+pub const PRICE_MGMT_ALGO: i32 = 151;